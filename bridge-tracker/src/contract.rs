@@ -2,10 +2,16 @@
 
 mod state;
 
-use bridge_tracker::{BridgeTrackerAbi, InstantiationArgument, Operation};
+use bridge_tracker::{
+    add_attestation, attested_deposit, deposit_attestation_message, evm, evm_amount,
+    limits::{QueuedTransfer, RollingWindow, TransferDirection},
+    bridged_asset, claim_held_deposits, link_signer, scan_chunks, BridgeTrackerAbi, DepositAttestation, DepositEvent,
+    InstantiationArgument, Operation, VoucherSignature, WithdrawalRequest, DEFAULT_SCAN_CHUNK_SIZE, VOUCHER_VALIDITY_SECS,
+};
+use dex::{BridgeToken, DexAbi, DexOperation, DexResponse};
 use linera_sdk::{
-    linera_base_types::WithContractAbi,
-    views::{RootView, View},
+    linera_base_types::{AccountOwner, Amount, ApplicationId, WithContractAbi},
+    views::{MapView, RootView, View, ViewStorageContext},
     Contract, ContractRuntime,
 };
 
@@ -42,6 +48,15 @@ impl Contract for BridgeTrackerContract {
         self.state.bridge_contract.set(argument.bridge_contract);
         self.state.usdc_contract.set(argument.usdc_contract);
         self.state.start_block.set(argument.start_block);
+        self.state.scan_cursor.set(argument.start_block);
+        let chunk_size = argument.scan_chunk_size.unwrap_or(DEFAULT_SCAN_CHUNK_SIZE);
+        assert!(chunk_size > 0, "Scan chunk size must be positive");
+        self.state.scan_chunk_size.set(chunk_size);
+        self.state.chain_id.set(argument.chain_id);
+        self.state.network.set(argument.network);
+        self.state.dex_app.set(argument.dex_app);
+        self.state.owner.set(argument.owner.or_else(|| self.runtime.authenticated_signer()));
+        self.set_relayers(argument.relayers, argument.attestation_threshold);
         
         self.state.save().await.expect("Failed to save state");
     }
@@ -51,8 +66,56 @@ impl Contract for BridgeTrackerContract {
             Operation::UpdateDeposits { to_block } => {
                 self.update_deposits(to_block).await;
             }
-            Operation::ProcessWithdrawal { user, token, amount, nonce } => {
-                self.process_withdrawal(user, token, amount, nonce).await;
+            Operation::ProcessWithdrawal { user, token, amount } => {
+                self.process_withdrawal(user, token, amount).await;
+            }
+            Operation::SignWithdrawal { nonce, signature } => {
+                self.sign_withdrawal(nonce, signature).await;
+            }
+            Operation::RefundWithdrawal { nonce } => {
+                self.refund_withdrawal(nonce).await;
+            }
+            Operation::SetScanChunkSize { blocks } => {
+                self.require_owner();
+                assert!(blocks > 0, "Scan chunk size must be positive");
+                self.state.scan_chunk_size.set(blocks);
+            }
+            Operation::SetDexApp { app } => {
+                self.require_owner();
+                self.state.dex_app.set(Some(app));
+            }
+            Operation::LinkEvmAddress { evm_address, signature } => {
+                self.link_evm_address(evm_address, signature).await;
+            }
+            Operation::ClaimDeposits { evm_address } => {
+                self.claim_deposits(evm_address).await;
+            }
+            Operation::SetRelayers { relayers, threshold } => {
+                self.require_owner();
+                self.set_relayers(relayers, threshold);
+            }
+            Operation::AttestDeposit { network, deposit, signature } => {
+                self.attest_deposit(network, deposit, signature).await;
+            }
+            Operation::FinalizeDeposit { nonce } => {
+                assert!(*self.state.attestation_threshold.get() > 0, "No relayer set is configured");
+                self.require_running(TransferDirection::Deposit);
+                self.finalize_deposit(nonce).await;
+            }
+            Operation::SetBridgeLimits { token, limits } => {
+                self.require_owner();
+                self.state.bridge_limits.insert(&token, limits).expect("Failed to store limits");
+            }
+            Operation::ReleaseQueued { direction, nonce } => {
+                self.require_owner();
+                self.release_queued(direction, nonce).await;
+            }
+            Operation::SetBridgePaused { direction, paused } => {
+                self.require_owner();
+                match direction {
+                    TransferDirection::Deposit => self.state.deposits_paused.set(paused),
+                    TransferDirection::Withdrawal => self.state.withdrawals_paused.set(paused),
+                }
             }
         }
     }
@@ -67,20 +130,379 @@ impl Contract for BridgeTrackerContract {
 }
 
 impl BridgeTrackerContract {
+    /// Scans forward from the cursor in chunks, advancing it past each chunk once its
+    /// deposits are recorded. A failed read reverts the whole operation.
     async fn update_deposits(&mut self, end_block: u64) {
+        assert_eq!(*self.state.attestation_threshold.get(), 0, "Deposits are minted from relayer attestations");
+        self.require_running(TransferDirection::Deposit);
+        let chunks = scan_chunks(*self.state.scan_cursor.get(), end_block, *self.state.scan_chunk_size.get());
+        for (from_block, to_block) in chunks {
+            for deposit in self.read_deposit_events(from_block, to_block) {
+                self.record_deposit(deposit).await;
+            }
+            self.state.scan_cursor.set(to_block);
+        }
+    }
+
+    fn read_deposit_events(&mut self, from_block: u64, to_block: u64) -> Vec<DepositEvent> {
         let request = async_graphql::Request::new(format!(
-            r#"query {{ readDepositEvents(endBlock: {end_block}) }}"#
+            r#"query {{ readDepositEvents(fromBlock: {from_block}, toBlock: {to_block}) {{ user token amount nonce block_number: blockNumber }} }}"#
         ));
 
         let application_id = self.runtime.application_id();
         let response = self.runtime.query_service(application_id, request);
+        if let Some(error) = response.errors.first() {
+            panic!("{}", error.message);
+        }
+        let async_graphql::Value::Object(mut data) = response.data else {
+            panic!("Unexpected deposit query response");
+        };
+        let events = data.swap_remove("readDepositEvents").expect("Missing deposit events");
+        async_graphql::from_value(events).expect("Malformed deposit events")
+    }
+
+    /// Asks the service whether the Vault has paid out withdrawal `nonce`
+    fn withdrawal_redeemed(&mut self, nonce: u64) -> bool {
+        let request = async_graphql::Request::new(format!("query {{ withdrawalRedeemed(nonce: {nonce}) }}"));
+        let application_id = self.runtime.application_id();
+        let response = self.runtime.query_service(application_id, request);
+        if let Some(error) = response.errors.first() {
+            panic!("{}", error.message);
+        }
+        let async_graphql::Value::Object(mut data) = response.data else {
+            panic!("Unexpected redemption query response");
+        };
+        let redeemed = data.swap_remove("withdrawalRedeemed").expect("Missing redemption status");
+        async_graphql::from_value(redeemed).expect("Malformed redemption status")
+    }
+
+    /// Records a deposit the first time it is seen and credits it
+    async fn record_deposit(&mut self, deposit: DepositEvent) {
+        let seen = self.state.deposits.contains_key(&deposit.nonce).await.expect("Failed to read deposits");
+        if seen {
+            return;
+        }
+        self.state.deposits.insert(&deposit.nonce, deposit.clone()).expect("Failed to store deposit");
+        self.credit_deposit(&deposit).await;
+    }
+
+    fn set_relayers(&mut self, relayers: Vec<String>, threshold: u32) {
+        let mut relayers = relayers
+            .iter()
+            .map(|relayer| evm::normalize_address(relayer).expect("Invalid relayer address"))
+            .collect::<Vec<_>>();
+        relayers.sort();
+        relayers.dedup();
+        assert!(threshold as usize <= relayers.len(), "Threshold exceeds the number of relayers");
+        self.state.relayers.set(relayers);
+        self.state.attestation_threshold.set(threshold);
+    }
+
+    /// Stores a relayer's attestation and mints the deposit once enough current
+    /// relayers agree on it. Attestations that disagree are kept for the
+    /// conflict query rather than rejected.
+    async fn attest_deposit(&mut self, network: String, deposit: DepositEvent, signature: String) {
+        assert!(*self.state.attestation_threshold.get() > 0, "No relayer set is configured");
+        self.require_running(TransferDirection::Deposit);
+        assert_eq!(network, *self.state.network.get(), "Deposit is for another network");
+        let deposit = deposit.normalized().expect("Malformed deposit");
+        let signature = evm::parse_signature(&signature).expect("Invalid signature");
+        let bridge: ApplicationId = self.runtime.application_id().forget_abi();
+        let message = deposit_attestation_message(&bridge, &network, &deposit);
+        let relayer = evm::recover_signer(&evm::personal_message_hash(message.as_bytes()), &signature)
+            .map(|address| evm::format_address(&address))
+            .expect("Invalid signature");
+
+        let nonce = deposit.nonce;
+        let mut attestations = self.state.attestations.get(&nonce).await.expect("Failed to read attestations").unwrap_or_default();
+        add_attestation(&mut attestations, self.state.relayers.get(), DepositAttestation { relayer, deposit })
+            .unwrap_or_else(|error| panic!("{error}"));
+        self.state.attestations.insert(&nonce, attestations).expect("Failed to store attestation");
+        self.finalize_deposit(nonce).await;
+    }
+
+    /// Mints a deposit if its stored attestations meet the current relayer set and threshold
+    async fn finalize_deposit(&mut self, nonce: u64) {
+        let threshold = *self.state.attestation_threshold.get() as usize;
+        let attestations = self.state.attestations.get(&nonce).await.expect("Failed to read attestations").unwrap_or_default();
+        if let Some(deposit) = attested_deposit(&attestations, self.state.relayers.get(), threshold) {
+            self.record_deposit(deposit.clone()).await;
+        }
+    }
+
+    /// Mints a deposit to the Linera owner linked to its EVM address, or holds it
+    /// until that address is linked
+    async fn credit_deposit(&mut self, deposit: &DepositEvent) {
+        let Some(user) = evm::normalize_address(&deposit.user) else {
+            return;
+        };
+        match self.state.evm_links.get(&user).await.expect("Failed to read links") {
+            Some(owner) => self.mint(owner, deposit).await,
+            None => {
+                let mut held = self.state.unclaimed_deposits.get(&user).await.expect("Failed to read deposits").unwrap_or_default();
+                held.push(deposit.nonce);
+                self.state.unclaimed_deposits.insert(&user, held).expect("Failed to store deposit");
+            }
+        }
+    }
+
+    /// Mints a deposit in the DEX, or queues it for release if it breaks a limit
+    async fn mint(&mut self, owner: AccountOwner, deposit: &DepositEvent) {
+        let Some((token, amount)) = self.bridged_deposit(deposit) else {
+            return;
+        };
+        if !self.admit(TransferDirection::Deposit, &token, amount).await {
+            self.queue(TransferDirection::Deposit, deposit.nonce, token, amount);
+            return;
+        }
+        self.mint_in_dex(owner, token, amount).await;
+    }
+
+    async fn mint_in_dex(&mut self, owner: AccountOwner, token: BridgeToken, amount: Amount) {
+        let operation = DexOperation::MintBridgeToken { token: token.clone(), user: owner, amount };
+        if let DexResponse::Error(error) = self.call_dex(operation) {
+            panic!("DEX rejected mint: {error}");
+        }
+        let supply = self.state.supply.get(&token).await.expect("Failed to read supply").unwrap_or_default();
+        self.state.supply.insert(&token, supply.saturating_add(amount)).expect("Failed to store supply");
+    }
+
+    fn call_dex(&mut self, operation: DexOperation) -> DexResponse {
+        let dex = self.state.dex_app.get().expect("DEX application not configured");
+        self.runtime.call_application(true, dex.with_abi::<DexAbi>(), &operation)
+    }
+
+    /// The DEX token and amount a deposit mints, if its token is bridged
+    fn bridged_deposit(&self, deposit: &DepositEvent) -> Option<(BridgeToken, Amount)> {
+        let (symbol, decimals) = bridged_asset(&deposit.token, self.state.usdc_contract.get())?;
+        let amount = evm_amount(&deposit.amount, decimals).expect("Invalid deposit amount");
+        Some((self.bridged_token(symbol), amount))
+    }
+
+    fn bridged_token(&self, symbol: &str) -> BridgeToken {
+        BridgeToken {
+            symbol: symbol.to_string(),
+            network: self.state.network.get().clone(),
+        }
+    }
+
+    fn require_running(&self, direction: TransferDirection) {
+        let paused = match direction {
+            TransferDirection::Deposit => *self.state.deposits_paused.get(),
+            TransferDirection::Withdrawal => *self.state.withdrawals_paused.get(),
+        };
+        assert!(!paused, "{direction:?}s are paused");
+    }
 
-        // Process deposit events and mint tokens
-        // Implementation will read Deposit events from bridge contract
+    /// Checks a transfer against its token's limits, counting it towards the daily
+    /// cap when it fits
+    async fn admit(&mut self, direction: TransferDirection, token: &BridgeToken, amount: Amount) -> bool {
+        let now = self.runtime.system_time().micros();
+        let limits = self.state.bridge_limits.get(token).await.expect("Failed to read limits").unwrap_or_default();
+        let flows = self.flow_window(direction);
+        let mut window = flows.get(token).await.expect("Failed to read flows").unwrap_or_default();
+        if !window.admit(&limits, direction, now, amount) {
+            return false;
+        }
+        flows.insert(token, window).expect("Failed to store flows");
+        true
+    }
+
+    async fn record_flow(&mut self, direction: TransferDirection, token: &BridgeToken, amount: Amount) {
+        let now = self.runtime.system_time().micros();
+        let flows = self.flow_window(direction);
+        let mut window = flows.get(token).await.expect("Failed to read flows").unwrap_or_default();
+        window.record(now, amount);
+        flows.insert(token, window).expect("Failed to store flows");
+    }
+
+    fn flow_window(&mut self, direction: TransferDirection) -> &mut MapView<ViewStorageContext, BridgeToken, RollingWindow> {
+        match direction {
+            TransferDirection::Deposit => &mut self.state.minted_window,
+            TransferDirection::Withdrawal => &mut self.state.withdrawn_window,
+        }
     }
 
-    async fn process_withdrawal(&mut self, user: String, token: String, amount: String, nonce: u64) {
-        // Burn tokens and emit withdrawal request
-        // Bridge operator will execute withdrawal on Ethereum
+    fn queue(&mut self, direction: TransferDirection, nonce: u64, token: BridgeToken, amount: Amount) {
+        let transfer = QueuedTransfer {
+            direction,
+            nonce,
+            token,
+            amount,
+            queued_at: self.runtime.system_time().micros(),
+        };
+        self.state.queued_transfers.insert(&(direction, nonce), transfer).expect("Failed to queue transfer");
+    }
+
+    /// Lets a queued transfer through. It still counts towards the daily cap.
+    async fn release_queued(&mut self, direction: TransferDirection, nonce: u64) {
+        self.require_running(direction);
+        let transfer = self
+            .state
+            .queued_transfers
+            .get(&(direction, nonce))
+            .await
+            .expect("Failed to read queue")
+            .expect("Transfer is not queued");
+        self.state.queued_transfers.remove(&(direction, nonce)).expect("Failed to update queue");
+        self.record_flow(direction, &transfer.token, transfer.amount).await;
+        if direction == TransferDirection::Deposit {
+            let deposit = self.state.deposits.get(&nonce).await.expect("Failed to read deposits").expect("Deposit exists");
+            let user = evm::normalize_address(&deposit.user).expect("Queued deposits have a valid user");
+            let owner = self
+                .state
+                .evm_links
+                .get(&user)
+                .await
+                .expect("Failed to read links")
+                .expect("Queued deposits are linked");
+            self.mint_in_dex(owner, transfer.token, transfer.amount).await;
+        } else {
+            // The voucher's validity starts when it can first be signed
+            let mut request = self.state.withdrawals.get(&nonce).await.expect("Failed to read withdrawals").expect("Withdrawal exists");
+            request.deadline = self.runtime.system_time().micros() / 1_000_000 + VOUCHER_VALIDITY_SECS;
+            self.state.withdrawals.insert(&nonce, request).expect("Failed to store withdrawal");
+        }
+    }
+
+    async fn link_evm_address(&mut self, evm_address: String, signature: String) {
+        let owner = self.runtime.authenticated_signer().expect("Linking requires an authenticated signer");
+        let address = evm::parse_address(&evm_address).expect("Invalid EVM address");
+        let signature = evm::parse_signature(&signature).expect("Invalid signature");
+        let evm_address = evm::format_address(&address);
+        let bridge: ApplicationId = self.runtime.application_id().forget_abi();
+        let nonce = self.state.link_nonces.get(&evm_address).await.expect("Failed to read link nonce").unwrap_or(0);
+        let signer = link_signer(&evm_address, &owner, &bridge, nonce, &signature);
+        assert_eq!(signer, Some(address), "Signature is not from {evm_address}");
+        self.state.evm_links.insert(&evm_address, owner).expect("Failed to store link");
+        self.state.link_nonces.insert(&evm_address, nonce + 1).expect("Failed to store link nonce");
+    }
+
+    /// Anyone may trigger a claim; the deposits only ever go to the linked owner
+    async fn claim_deposits(&mut self, evm_address: String) {
+        self.require_running(TransferDirection::Deposit);
+        let evm_address = evm::normalize_address(&evm_address).expect("Invalid EVM address");
+        let link = self.state.evm_links.get(&evm_address).await.expect("Failed to read links");
+        let held = self.state.unclaimed_deposits.get(&evm_address).await.expect("Failed to read deposits");
+        let (owner, held) = claim_held_deposits(link, held).expect("EVM address is not linked");
+        self.state.unclaimed_deposits.remove(&evm_address).expect("Failed to clear deposits");
+        for nonce in held {
+            let deposit = self.state.deposits.get(&nonce).await.expect("Failed to read deposits").expect("Held deposit exists");
+            self.mint(owner, &deposit).await;
+        }
+    }
+
+    fn require_owner(&mut self) {
+        let signer = self.runtime.authenticated_signer();
+        assert!(signer.is_some() && signer == *self.state.owner.get(), "Only the owner can do this");
+    }
+
+    /// Burns the signer's tokens in the DEX and opens a voucher for relayers to sign
+    async fn process_withdrawal(&mut self, user: String, token: String, amount: String) {
+        let owner = self.runtime.authenticated_signer().expect("Withdrawals require an authenticated signer");
+        assert!(*self.state.attestation_threshold.get() > 0, "No relayer set is configured");
+        self.require_running(TransferDirection::Withdrawal);
+        let user = evm::normalize_address(&user).expect("Invalid EVM address");
+        let token = evm::normalize_address(&token).expect("Invalid token address");
+        let (symbol, decimals) = bridged_asset(&token, self.state.usdc_contract.get()).expect("Token is not bridged");
+        let burned = evm_amount(&amount, decimals).filter(|amount| !amount.is_zero()).expect("Invalid amount");
+        let bridged = self.bridged_token(symbol);
+        let operation = DexOperation::BurnBridgeToken { token: bridged.clone(), user: owner, amount: burned };
+        if let DexResponse::Error(error) = self.call_dex(operation) {
+            panic!("DEX rejected withdrawal: {error}");
+        }
+        let supply = self.state.supply.get(&bridged).await.expect("Failed to read supply").unwrap_or_default();
+        self.state.supply.insert(&bridged, supply.saturating_sub(burned)).expect("Failed to store supply");
+
+        let nonce = *self.state.next_withdrawal_nonce.get();
+        self.state.next_withdrawal_nonce.set(nonce + 1);
+        if !self.admit(TransferDirection::Withdrawal, &bridged, burned).await {
+            self.queue(TransferDirection::Withdrawal, nonce, bridged, burned);
+        }
+        let deadline = self.runtime.system_time().micros() / 1_000_000 + VOUCHER_VALIDITY_SECS;
+        let request = WithdrawalRequest {
+            user,
+            token,
+            amount: amount.parse::<u128>().expect("Invalid amount").to_string(),
+            nonce,
+            processed: false,
+            owner,
+            deadline,
+            signatures: Vec::new(),
+            refunded: false,
+        };
+        self.state.withdrawals.insert(&nonce, request).expect("Failed to store withdrawal");
+    }
+
+    /// Mints an expired withdrawal back to its owner once the Vault confirms it was
+    /// never paid out, since the burned tokens would otherwise be lost
+    async fn refund_withdrawal(&mut self, nonce: u64) {
+        let mut request = self
+            .state
+            .withdrawals
+            .get(&nonce)
+            .await
+            .expect("Failed to read withdrawals")
+            .expect("Withdrawal does not exist");
+        let queued = self
+            .state
+            .queued_transfers
+            .contains_key(&(TransferDirection::Withdrawal, nonce))
+            .await
+            .expect("Failed to read queue");
+        let now = self.runtime.system_time().micros() / 1_000_000;
+        request
+            .check_refund(self.runtime.authenticated_signer(), queued, now)
+            .unwrap_or_else(|error| panic!("{error}"));
+        assert!(!self.withdrawal_redeemed(nonce), "Withdrawal was paid out by the Vault");
+
+        let (symbol, decimals) = bridged_asset(&request.token, self.state.usdc_contract.get()).expect("Token is not bridged");
+        let amount = evm_amount(&request.amount, decimals).expect("Invalid amount");
+        let token = self.bridged_token(symbol);
+        request.refunded = true;
+        let owner = request.owner;
+        self.state.withdrawals.insert(&nonce, request).expect("Failed to store withdrawal");
+        self.mint_in_dex(owner, token, amount).await;
+    }
+
+    /// Adds a relayer's signature to a withdrawal voucher; the voucher is complete once
+    /// the threshold of current relayers have signed it
+    async fn sign_withdrawal(&mut self, nonce: u64, signature: String) {
+        self.require_running(TransferDirection::Withdrawal);
+        let queued = self
+            .state
+            .queued_transfers
+            .contains_key(&(TransferDirection::Withdrawal, nonce))
+            .await
+            .expect("Failed to read queue");
+        assert!(!queued, "Withdrawal is queued for release");
+        let mut request = self
+            .state
+            .withdrawals
+            .get(&nonce)
+            .await
+            .expect("Failed to read withdrawals")
+            .expect("Withdrawal does not exist");
+        assert!(!request.refunded, "Withdrawal was refunded");
+        let now = self.runtime.system_time().micros() / 1_000_000;
+        assert!(now <= request.deadline, "Voucher has expired");
+        let digest = request
+            .voucher_digest(*self.state.chain_id.get(), self.state.bridge_contract.get())
+            .expect("Malformed voucher");
+        let parsed = evm::parse_signature(&signature).expect("Invalid signature");
+        let relayer = evm::recover_signer(&digest, &parsed)
+            .map(|address| evm::format_address(&address))
+            .expect("Invalid signature");
+        let relayers = self.state.relayers.get().clone();
+        assert!(relayers.contains(&relayer), "Signer is not a relayer");
+        assert!(
+            request.signatures.iter().all(|signed| signed.relayer != relayer),
+            "Relayer already signed this voucher"
+        );
+
+        request.signatures.push(VoucherSignature { relayer, signature: format!("0x{}", hex::encode(parsed)) });
+        let signed = request.signatures.iter().filter(|signed| relayers.contains(&signed.relayer)).count();
+        request.processed = signed >= *self.state.attestation_threshold.get() as usize;
+        self.state.withdrawals.insert(&nonce, request).expect("Failed to store withdrawal");
     }
 }
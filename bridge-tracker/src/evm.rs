@@ -0,0 +1,117 @@
+//! Ethereum address handling and signature recovery for bridge authorizations
+
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use sha3::{Digest, Keccak256};
+
+pub type EvmAddress = [u8; 20];
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// Parses a `0x`-prefixed hex address, in any letter case
+pub fn parse_address(address: &str) -> Option<EvmAddress> {
+    let digits = address.strip_prefix("0x").or_else(|| address.strip_prefix("0X"))?;
+    hex::decode(digits).ok()?.try_into().ok()
+}
+
+/// Lowercase `0x`-prefixed hex, the form addresses are stored under
+pub fn format_address(address: &EvmAddress) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+pub fn normalize_address(address: &str) -> Option<String> {
+    parse_address(address).map(|address| format_address(&address))
+}
+
+/// Parses a `0x`-prefixed hex signature in the 65-byte `r || s || v` form wallets produce
+pub fn parse_signature(signature: &str) -> Option<[u8; 65]> {
+    let digits = signature.strip_prefix("0x").unwrap_or(signature);
+    hex::decode(digits).ok()?.try_into().ok()
+}
+
+/// Digest of an EIP-191 `personal_sign` message
+pub fn personal_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut data = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    data.extend_from_slice(message);
+    keccak256(&data)
+}
+
+/// Recovers the address whose key signed `digest`. Rejects high-s signatures,
+/// as Ethereum does, so each authorization has exactly one valid encoding.
+pub fn recover_signer(digest: &[u8; 32], signature: &[u8; 65]) -> Option<EvmAddress> {
+    let recovery_id = match signature[64] {
+        v @ (0 | 1) => v,
+        v @ (27 | 28) => v - 27,
+        _ => return None,
+    };
+    let parsed = Signature::from_slice(&signature[..64]).ok()?;
+    if parsed.normalize_s().is_some() {
+        return None;
+    }
+    let key = VerifyingKey::recover_from_prehash(digest, &parsed, RecoveryId::from_byte(recovery_id)?).ok()?;
+    let point = key.to_encoded_point(false);
+    keccak256(&point.as_bytes()[1..])[12..].try_into().ok()
+}
+
+/// An address as a 32-byte ABI word
+pub fn address_word(address: &EvmAddress) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[12..].copy_from_slice(address);
+    word
+}
+
+/// An unsigned integer as a 32-byte ABI word
+pub fn uint_word(value: u128) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Hash of ABI-encoded 32-byte words, as `keccak256(abi.encode(...))` computes it
+pub fn hash_words(words: &[[u8; 32]]) -> [u8; 32] {
+    keccak256(&words.concat())
+}
+
+/// EIP-712 separator of a domain declared as
+/// `EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)`
+pub fn domain_separator(name: &str, version: &str, chain_id: u64, verifying_contract: &EvmAddress) -> [u8; 32] {
+    hash_words(&[
+        keccak256(b"EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)"),
+        keccak256(name.as_bytes()),
+        keccak256(version.as_bytes()),
+        uint_word(chain_id.into()),
+        address_word(verifying_contract),
+    ])
+}
+
+/// Digest an EIP-712 signer signs for a struct with hash `struct_hash`
+pub fn typed_data_hash(domain_separator: &[u8; 32], struct_hash: &[u8; 32]) -> [u8; 32] {
+    let mut data = vec![0x19, 0x01];
+    data.extend_from_slice(domain_separator);
+    data.extend_from_slice(struct_hash);
+    keccak256(&data)
+}
+
+/// Calldata for an ERC20 `balanceOf(owner)` call
+pub fn balance_of_call(owner: &EvmAddress) -> Vec<u8> {
+    let mut data = keccak256(b"balanceOf(address)")[..4].to_vec();
+    data.extend_from_slice(&address_word(owner));
+    data
+}
+
+/// Calldata for the Vault's `usedWithdrawals(nonce)` getter
+pub fn used_withdrawals_call(nonce: u64) -> Vec<u8> {
+    let mut data = keccak256(b"usedWithdrawals(uint256)")[..4].to_vec();
+    data.extend_from_slice(&uint_word(nonce.into()));
+    data
+}
+
+/// Decodes a returned `uint256`, if it fits in 128 bits
+pub fn decode_uint(word: &[u8]) -> Option<u128> {
+    let word: &[u8; 32] = word.try_into().ok()?;
+    if word[..16].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(u128::from_be_bytes(word[16..].try_into().ok()?))
+}
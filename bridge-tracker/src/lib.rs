@@ -1,8 +1,16 @@
-use async_graphql::{Request, Response, SimpleObject};
+pub mod evm;
+pub mod limits;
+
+use async_graphql::{InputObject, Request, Response, SimpleObject};
+use dex::{BridgeToken, BASE_TOKEN_SYMBOL};
 use linera_sdk::{
-    linera_base_types::{ContractAbi, ServiceAbi},
+    graphql::GraphQLMutationRoot,
+    linera_base_types::{AccountOwner, Amount, ApplicationId, ContractAbi, ServiceAbi},
 };
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::limits::{BridgeLimits, TransferDirection};
 
 pub struct BridgeTrackerAbi;
 
@@ -16,16 +24,68 @@ impl ServiceAbi for BridgeTrackerAbi {
     type QueryResponse = Response;
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, GraphQLMutationRoot)]
 pub enum Operation {
-    /// Update deposits by reading bridge contract events
+    /// Scan bridge contract events for deposits from the scan cursor up to, but not
+    /// including, `to_block`. Each call covers at most `MAX_SCAN_CHUNKS` chunks.
     UpdateDeposits { to_block: u64 },
-    /// Process withdrawal request
+    /// Burn the signer's bridged tokens and open a withdrawal voucher paying `amount`
+    /// (in the EVM token's units) of `token` to the EVM address `user`. Ether uses
+    /// `ETH_TOKEN`, and its voucher is redeemed with the Vault's `withdrawETH`.
     ProcessWithdrawal { 
         user: String,
         token: String, 
         amount: String,
-        nonce: u64 
+    },
+    /// Set how many blocks each log query covers (owner only)
+    SetScanChunkSize { blocks: u64 },
+    /// Set the DEX that deposits are minted into (owner only)
+    SetDexApp { app: ApplicationId },
+    /// Link an EVM address to the signer, proven by an EIP-191 signature of
+    /// `link_message` from that address's key
+    LinkEvmAddress {
+        evm_address: String,
+        signature: String,
+    },
+    /// Mint the deposits held for a linked EVM address to its Linera owner
+    ClaimDeposits { evm_address: String },
+    /// Replace the relayer EVM addresses and how many must attest to a deposit or
+    /// sign a withdrawal voucher (owner only). A threshold of zero trusts `UpdateDeposits` instead.
+    SetRelayers {
+        relayers: Vec<String>,
+        threshold: u32,
+    },
+    /// Submit a relayer's EIP-712 signature of a withdrawal voucher
+    SignWithdrawal {
+        nonce: u64,
+        signature: String,
+    },
+    /// Re-mint an expired withdrawal the Vault never paid out to the account it was
+    /// burned from (that account only)
+    RefundWithdrawal { nonce: u64 },
+    /// Submit a relayer's EIP-191 signature of `deposit_attestation_message`
+    AttestDeposit {
+        network: String,
+        deposit: DepositEvent,
+        signature: String,
+    },
+    /// Mint a deposit whose stored attestations meet the current threshold, e.g. after
+    /// `SetRelayers` lowered it or removed a dissenting relayer
+    FinalizeDeposit { nonce: u64 },
+    /// Set the limits on a bridged token (owner only)
+    SetBridgeLimits {
+        token: BridgeToken,
+        limits: BridgeLimits,
+    },
+    /// Mint a queued deposit or unblock a queued withdrawal despite its limits (owner only)
+    ReleaseQueued {
+        direction: TransferDirection,
+        nonce: u64,
+    },
+    /// Stop or resume one direction of the bridge (owner only)
+    SetBridgePaused {
+        direction: TransferDirection,
+        paused: bool,
     },
 }
 
@@ -39,9 +99,129 @@ pub struct InstantiationArgument {
     pub usdc_contract: String,
     /// Starting block number
     pub start_block: u64,
+    /// Blocks per log query; defaults to `DEFAULT_SCAN_CHUNK_SIZE`
+    #[serde(default)]
+    pub scan_chunk_size: Option<u64>,
+    /// EVM chain ID of the network, which withdrawal vouchers are bound to
+    pub chain_id: u64,
+    /// Network tag of the tokens minted for deposits, e.g. "sepolia"
+    pub network: String,
+    /// The DEX that deposits are minted into; can also be set later with `SetDexApp`
+    #[serde(default)]
+    pub dex_app: Option<ApplicationId>,
+    /// Defaults to the account that instantiates the tracker
+    #[serde(default)]
+    pub owner: Option<AccountOwner>,
+    /// EVM addresses of the relayers that attest to deposits
+    #[serde(default)]
+    pub relayers: Vec<String>,
+    /// Matching relayer attestations needed to mint a deposit
+    #[serde(default)]
+    pub attestation_threshold: u32,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+/// Blocks per log query unless configured, within common RPC range limits
+pub const DEFAULT_SCAN_CHUNK_SIZE: u64 = 2_000;
+/// Chunks one `UpdateDeposits` may scan, bounding the work in a single block
+pub const MAX_SCAN_CHUNKS: u64 = 10;
+
+/// The block ranges one `UpdateDeposits` scans, each ending before its upper bound:
+/// consecutive chunks of `chunk_size` blocks from `cursor`, the last one cut short at
+/// `end_block`, and at most `MAX_SCAN_CHUNKS` of them
+pub fn scan_chunks(cursor: u64, end_block: u64, chunk_size: u64) -> Vec<(u64, u64)> {
+    let mut chunks = Vec::new();
+    let mut from_block = cursor;
+    while from_block < end_block && (chunks.len() as u64) < MAX_SCAN_CHUNKS {
+        let to_block = end_block.min(from_block.saturating_add(chunk_size));
+        chunks.push((from_block, to_block));
+        from_block = to_block;
+    }
+    chunks
+}
+
+/// Decimals of USDC on EVM networks
+pub const USDC_DECIMALS: u8 = 6;
+
+/// Symbol of the token minted for ether held by the Vault
+pub const WETH_SYMBOL: &str = "wETH";
+pub const ETH_DECIMALS: u8 = 18;
+
+/// The token field of Vault deposits and vouchers for ether
+pub const ETH_TOKEN: &str = "0x0000000000000000000000000000000000000000";
+
+/// Symbol and EVM decimals of the token minted for a Vault asset, if it is bridged
+pub fn bridged_asset(token: &str, usdc_contract: &str) -> Option<(&'static str, u8)> {
+    let token = evm::normalize_address(token)?;
+    if Some(&token) == evm::normalize_address(usdc_contract).as_ref() {
+        Some((BASE_TOKEN_SYMBOL, USDC_DECIMALS))
+    } else if token == ETH_TOKEN {
+        Some((WETH_SYMBOL, ETH_DECIMALS))
+    } else {
+        None
+    }
+}
+
+/// How long a withdrawal voucher can be redeemed on the Vault
+pub const VOUCHER_VALIDITY_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// How long past a voucher's deadline a refund waits, covering clock drift between
+/// Linera and Ethereum block times
+pub const REFUND_GRACE_SECS: u64 = 60 * 60;
+
+/// EIP-712 domain name and version the Vault verifies vouchers under
+pub const VAULT_DOMAIN_NAME: &str = "FastDEX Vault";
+pub const VAULT_DOMAIN_VERSION: &str = "1";
+
+const WITHDRAWAL_TYPE: &str = "Withdrawal(address user,address token,uint256 amount,uint256 nonce,uint256 deadline)";
+
+/// The text an EVM key signs to link its address to a Linera owner on this bridge.
+/// `nonce` counts the address's earlier links, so an old signature cannot be replayed.
+pub fn link_message(evm_address: &str, owner: &AccountOwner, bridge: &ApplicationId, nonce: u64) -> String {
+    format!("Link {evm_address} to Linera account {owner} on FastDEX bridge {bridge} (link #{nonce})")
+}
+
+/// The address whose key signed the `link_message` linking `evm_address` to `owner`.
+/// Only a signature of the address's current link `nonce` recovers to the address itself.
+pub fn link_signer(
+    evm_address: &str,
+    owner: &AccountOwner,
+    bridge: &ApplicationId,
+    nonce: u64,
+    signature: &[u8; 65],
+) -> Option<evm::EvmAddress> {
+    let message = link_message(evm_address, owner, bridge, nonce);
+    evm::recover_signer(&evm::personal_message_hash(message.as_bytes()), signature)
+}
+
+/// The owner a claim for an EVM address mints to and the held deposits it releases;
+/// nothing is released until the address is linked
+pub fn claim_held_deposits(link: Option<AccountOwner>, held: Option<Vec<u64>>) -> Option<(AccountOwner, Vec<u64>)> {
+    Some((link?, held.unwrap_or_default()))
+}
+
+/// The text a relayer signs to attest that `deposit` happened on `network`
+pub fn deposit_attestation_message(bridge: &ApplicationId, network: &str, deposit: &DepositEvent) -> String {
+    format!(
+        "FastDEX deposit attestation\nbridge: {bridge}\nnetwork: {network}\nnonce: {}\nuser: {}\ntoken: {}\namount: {}\nblock: {}",
+        deposit.nonce, deposit.user, deposit.token, deposit.amount, deposit.block_number,
+    )
+}
+
+/// Converts an integer amount of an EVM token with `decimals` decimals to an `Amount`
+pub fn evm_amount(raw: &str, decimals: u8) -> Option<Amount> {
+    let raw = raw.parse::<u128>().ok()?;
+    let scale = 10u128.checked_pow(u32::from(Amount::DECIMAL_PLACES.checked_sub(decimals)?))?;
+    raw.checked_mul(scale).map(Amount::from_attos)
+}
+
+/// How far the Vault's `collateral` exceeds wrapped `supply` and how far it falls short,
+/// as `(surplus, deficit)`; at most one is non-zero
+pub fn collateral_gap(supply: Amount, collateral: Amount) -> (Amount, Amount) {
+    (collateral.saturating_sub(supply), supply.saturating_sub(collateral))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, SimpleObject, InputObject)]
+#[graphql(input_name = "DepositEventInput")]
 pub struct DepositEvent {
     pub user: String,
     pub token: String,
@@ -50,11 +230,155 @@ pub struct DepositEvent {
     pub block_number: u64,
 }
 
+impl DepositEvent {
+    /// The deposit with its addresses and amount in canonical form, so attestations
+    /// from different relayers compare equal
+    pub fn normalized(&self) -> Option<DepositEvent> {
+        Some(DepositEvent {
+            user: evm::normalize_address(&self.user)?,
+            token: evm::normalize_address(&self.token)?,
+            amount: self.amount.parse::<u128>().ok()?.to_string(),
+            nonce: self.nonce,
+            block_number: self.block_number,
+        })
+    }
+}
+
+/// One relayer's account of a deposit
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct DepositAttestation {
+    pub relayer: String,
+    pub deposit: DepositEvent,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AttestationError {
+    #[error("Signer is not a relayer")]
+    NotRelayer,
+    #[error("Relayer already attested to this deposit")]
+    AlreadyAttested,
+}
+
+/// Adds a current relayer's first attestation to those stored for a deposit
+pub fn add_attestation(
+    attestations: &mut Vec<DepositAttestation>,
+    relayers: &[String],
+    attestation: DepositAttestation,
+) -> Result<(), AttestationError> {
+    if !relayers.contains(&attestation.relayer) {
+        return Err(AttestationError::NotRelayer);
+    }
+    if attestations.iter().any(|existing| existing.relayer == attestation.relayer) {
+        return Err(AttestationError::AlreadyAttested);
+    }
+    attestations.push(attestation);
+    Ok(())
+}
+
+/// The version of a deposit that at least `threshold` current relayers attested to.
+/// Attestations from removed relayers don't count, and nothing is returned while more
+/// than one version meets the threshold.
+pub fn attested_deposit<'a>(
+    attestations: &'a [DepositAttestation],
+    relayers: &[String],
+    threshold: usize,
+) -> Option<&'a DepositEvent> {
+    if threshold == 0 {
+        return None;
+    }
+    let current = attestations
+        .iter()
+        .filter(|attestation| relayers.contains(&attestation.relayer))
+        .collect::<Vec<_>>();
+    let mut agreed = current.iter().map(|attestation| &attestation.deposit).filter(|deposit| {
+        current.iter().filter(|attestation| attestation.deposit == **deposit).count() >= threshold
+    });
+    let deposit = agreed.next()?;
+    agreed.all(|other| other == deposit).then_some(deposit)
+}
+
+/// A relayer's signature on a withdrawal voucher
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct VoucherSignature {
+    pub relayer: String,
+    pub signature: String,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
 pub struct WithdrawalRequest {
+    pub user: String, // EVM recipient
+    pub token: String,
+    pub amount: String,
+    pub nonce: u64,
+    pub processed: bool, // Enough relayers have signed the voucher
+    pub owner: AccountOwner, // Linera account the tokens were burned from
+    pub deadline: u64, // Unix seconds
+    pub signatures: Vec<VoucherSignature>,
+    #[serde(default)]
+    pub refunded: bool, // Re-minted to `owner` after the voucher expired unused
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RefundError {
+    #[error("Only the withdrawing account can do this")]
+    NotOwner,
+    #[error("Withdrawal was already refunded")]
+    AlreadyRefunded,
+    #[error("Withdrawal is queued for release")]
+    Queued,
+    #[error("Voucher has not expired")]
+    NotExpired,
+}
+
+impl WithdrawalRequest {
+    /// Whether `caller` may refund the withdrawal at `now` (Unix seconds): only its owner,
+    /// once, after the voucher's deadline and the grace period have passed. A queued
+    /// withdrawal was never signable, so it is released rather than refunded.
+    pub fn check_refund(&self, caller: Option<AccountOwner>, queued: bool, now: u64) -> Result<(), RefundError> {
+        if caller != Some(self.owner) {
+            return Err(RefundError::NotOwner);
+        }
+        if self.refunded {
+            return Err(RefundError::AlreadyRefunded);
+        }
+        if queued {
+            return Err(RefundError::Queued);
+        }
+        if now <= self.deadline.saturating_add(REFUND_GRACE_SECS) {
+            return Err(RefundError::NotExpired);
+        }
+        Ok(())
+    }
+
+    /// EIP-712 digest of the withdrawal voucher for the Vault at `vault` on `chain_id`
+    pub fn voucher_digest(&self, chain_id: u64, vault: &str) -> Option<[u8; 32]> {
+        let domain = evm::domain_separator(VAULT_DOMAIN_NAME, VAULT_DOMAIN_VERSION, chain_id, &evm::parse_address(vault)?);
+        let withdrawal = evm::hash_words(&[
+            evm::keccak256(WITHDRAWAL_TYPE.as_bytes()),
+            evm::address_word(&evm::parse_address(&self.user)?),
+            evm::address_word(&evm::parse_address(&self.token)?),
+            evm::uint_word(self.amount.parse().ok()?),
+            evm::uint_word(self.nonce.into()),
+            evm::uint_word(self.deadline.into()),
+        ]);
+        Some(evm::typed_data_hash(&domain, &withdrawal))
+    }
+}
+
+/// Everything the Vault's withdraw functions need, with relayer signatures in
+/// ascending signer order
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct WithdrawalVoucher {
     pub user: String,
     pub token: String,
     pub amount: String,
     pub nonce: u64,
-    pub processed: bool,
+    pub deadline: u64,
+    pub chain_id: u64,
+    pub vault: String,
+    pub digest: String,
+    pub signatures: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,76 @@
+use async_graphql::{Enum, InputObject, SimpleObject};
+use dex::BridgeToken;
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Length of the rolling window daily caps apply to
+pub const WINDOW_MICROS: u64 = 24 * 60 * 60 * 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, Enum)]
+pub enum TransferDirection {
+    Deposit,
+    Withdrawal,
+}
+
+/// Limits on how much of one bridged token may move; `None` leaves a limit off
+#[derive(Debug, Clone, Default, Deserialize, Serialize, SimpleObject, InputObject)]
+#[graphql(input_name = "BridgeLimitsInput")]
+pub struct BridgeLimits {
+    pub max_deposit: Option<Amount>,
+    pub daily_mint_cap: Option<Amount>,
+    pub daily_withdrawal_cap: Option<Amount>,
+}
+
+impl BridgeLimits {
+    /// Whether `amount` fits within the limits for `direction`, given what already
+    /// moved that way in the last 24 hours
+    pub fn allows(&self, direction: TransferDirection, amount: Amount, moved_today: Amount) -> bool {
+        let (single, daily) = match direction {
+            TransferDirection::Deposit => (self.max_deposit, self.daily_mint_cap),
+            TransferDirection::Withdrawal => (None, self.daily_withdrawal_cap),
+        };
+        single.is_none_or(|max| amount <= max) && daily.is_none_or(|cap| moved_today.saturating_add(amount) <= cap)
+    }
+}
+
+/// Amounts moved over the last 24 hours
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RollingWindow {
+    entries: Vec<(u64, Amount)>, // (timestamp, amount), oldest first
+}
+
+impl RollingWindow {
+    pub fn total(&self, now: u64) -> Amount {
+        let start = now.saturating_sub(WINDOW_MICROS);
+        self.entries
+            .iter()
+            .filter(|(timestamp, _)| *timestamp > start)
+            .fold(Amount::ZERO, |total, (_, amount)| total.saturating_add(*amount))
+    }
+
+    pub fn record(&mut self, now: u64, amount: Amount) {
+        let start = now.saturating_sub(WINDOW_MICROS);
+        self.entries.retain(|(timestamp, _)| *timestamp > start);
+        self.entries.push((now, amount));
+    }
+
+    /// Records a transfer if it fits within `limits`. One that doesn't is left out of
+    /// the window for the caller to queue.
+    pub fn admit(&mut self, limits: &BridgeLimits, direction: TransferDirection, now: u64, amount: Amount) -> bool {
+        if !limits.allows(direction, amount, self.total(now)) {
+            return false;
+        }
+        self.record(now, amount);
+        true
+    }
+}
+
+/// A deposit or withdrawal held back by a limit until the owner releases it
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct QueuedTransfer {
+    pub direction: TransferDirection,
+    pub nonce: u64,
+    pub token: BridgeToken,
+    pub amount: Amount,
+    pub queued_at: u64,
+}
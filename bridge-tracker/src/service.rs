@@ -3,17 +3,28 @@
 mod state;
 
 use std::sync::Arc;
-use async_graphql::{EmptySubscription, Request, Response, Schema};
-use bridge_tracker::Operation;
+use async_graphql::{EmptySubscription, Enum, Request, Response, Schema, SimpleObject};
+use bridge_tracker::{
+    evm,
+    limits::{BridgeLimits, QueuedTransfer, TransferDirection},
+    bridged_asset, collateral_gap, evm_amount, link_message, DepositAttestation, DepositEvent, Operation, WithdrawalRequest,
+    WithdrawalVoucher, ETH_DECIMALS, USDC_DECIMALS, WETH_SYMBOL,
+};
+use dex::{BridgeToken, BASE_TOKEN_SYMBOL};
 use linera_sdk::{
     ethereum::{EthereumDataType, EthereumEvent, EthereumQueries, ServiceEthereumClient},
-    linera_base_types::WithServiceAbi,
+    linera_base_types::{AccountOwner, Amount, WithServiceAbi},
+    graphql::GraphQLMutationRoot,
     views::View,
     Service, ServiceRuntime,
 };
 
 use self::state::BridgeTrackerState;
 
+/// Tries per block range before an RPC failure is reported
+const SCAN_ATTEMPTS: u32 = 3;
+const MAX_PAGE_SIZE: usize = 100;
+
 #[derive(Clone)]
 pub struct BridgeTrackerService {
     state: Arc<BridgeTrackerState>,
@@ -44,7 +55,7 @@ impl Service for BridgeTrackerService {
             QueryRoot {
                 service: self.clone(),
             },
-            MutationRoot,
+            Operation::mutation_root(self.runtime.clone()),
             EmptySubscription,
         )
         .finish();
@@ -56,31 +67,119 @@ struct QueryRoot {
     service: BridgeTrackerService,
 }
 
+impl QueryRoot {
+    async fn deposit_record(&self, deposit: DepositEvent) -> DepositRecord {
+        let state = &self.service.state;
+        let queued = state
+            .queued_transfers
+            .contains_key(&(TransferDirection::Deposit, deposit.nonce))
+            .await
+            .unwrap_or(false);
+        let user = evm::normalize_address(&deposit.user);
+        let status = if queued {
+            DepositStatus::Queued
+        } else if user.is_none() || bridged_asset(&deposit.token, state.usdc_contract.get()).is_none() {
+            DepositStatus::Ignored
+        } else {
+            let held = match &user {
+                Some(user) => state.unclaimed_deposits.get(user).await.ok().flatten().unwrap_or_default(),
+                None => Vec::new(),
+            };
+            if held.contains(&deposit.nonce) {
+                DepositStatus::Unclaimed
+            } else {
+                DepositStatus::Minted
+            }
+        };
+        DepositRecord { deposit, status }
+    }
+
+    async fn withdrawal_record(&self, request: WithdrawalRequest) -> WithdrawalRecord {
+        let queued = self
+            .service
+            .state
+            .queued_transfers
+            .contains_key(&(TransferDirection::Withdrawal, request.nonce))
+            .await
+            .unwrap_or(false);
+        let now = self.service.runtime.system_time().micros() / 1_000_000;
+        let status = if request.refunded {
+            WithdrawalStatus::Refunded
+        } else if queued {
+            WithdrawalStatus::Queued
+        } else if request.deadline < now {
+            WithdrawalStatus::Expired
+        } else if !request.processed {
+            WithdrawalStatus::AwaitingSignatures
+        } else {
+            WithdrawalStatus::Ready
+        };
+        WithdrawalRecord { request, status }
+    }
+}
+
 #[async_graphql::Object]
 impl QueryRoot {
-    async fn read_deposit_events(&self, end_block: u64) -> Vec<bridge_tracker::DepositEvent> {
+    /// Deposit events in blocks `from_block..to_block`, retrying transient RPC failures
+    async fn read_deposit_events(&self, from_block: u64, to_block: u64) -> async_graphql::Result<Vec<DepositEvent>> {
         let url = self.service.state.ethereum_endpoint.get().clone();
         let contract_address = self.service.state.bridge_contract.get().clone();
-        let start_block = *self.service.state.start_block.get();
 
         let client = ServiceEthereumClient::new(url);
         
         // Read Deposit events from bridge contract
         let event_signature = "Deposit(address indexed,address indexed,uint256,uint256 indexed)";
         
-        match client.read_events(&contract_address, event_signature, start_block, end_block).await {
-            Ok(events) => {
-                events.into_iter().map(|event| {
-                    bridge_tracker::DepositEvent {
-                        user: format!("{:?}", event.values[0]),
-                        token: format!("{:?}", event.values[1]), 
-                        amount: format!("{:?}", event.values[2]),
-                        nonce: format!("{:?}", event.values[3]).parse().unwrap_or(0),
-                        block_number: event.block_number,
-                    }
-                }).collect()
+        let mut attempt = 1;
+        let events = loop {
+            match client.read_events(&contract_address, event_signature, from_block, to_block).await {
+                Ok(events) => break events,
+                Err(error) if attempt == SCAN_ATTEMPTS => {
+                    return Err(format!("Reading blocks {from_block}..{to_block} failed: {error:?}").into());
+                }
+                Err(_) => attempt += 1,
             }
-            Err(_) => vec![]
+        };
+        Ok(events
+            .into_iter()
+            .map(|event| DepositEvent {
+                user: decode_value(&event.values[0]),
+                token: decode_value(&event.values[1]),
+                amount: decode_value(&event.values[2]),
+                nonce: decode_value(&event.values[3]).parse().unwrap_or(0),
+                block_number: event.block_number,
+            })
+            .collect())
+    }
+
+    /// Whether the Vault has paid out withdrawal `nonce`, read at the latest block
+    async fn withdrawal_redeemed(&self, nonce: u64) -> async_graphql::Result<bool> {
+        let state = &self.service.state;
+        let client = ServiceEthereumClient::new(state.ethereum_endpoint.get().clone());
+        let vault = state.bridge_contract.get().clone();
+        let block = client
+            .get_block_number()
+            .await
+            .map_err(|error| format!("Reading the block number failed: {error:?}"))?;
+        let output = client
+            .non_executive_call(&vault, evm::used_withdrawals_call(nonce).into(), &vault, block)
+            .await
+            .map_err(|error| format!("Reading withdrawal {nonce} failed: {error:?}"))?;
+        let used = evm::decode_uint(&output).ok_or("Malformed usedWithdrawals result")?;
+        Ok(used != 0)
+    }
+
+    /// How far deposit scanning has got and how far it trails the chain head
+    async fn scan_progress(&self) -> ScanProgress {
+        let state = &self.service.state;
+        let client = ServiceEthereumClient::new(state.ethereum_endpoint.get().clone());
+        let cursor = *state.scan_cursor.get();
+        let chain_head = client.get_block_number().await.ok();
+        ScanProgress {
+            cursor,
+            chunk_size: *state.scan_chunk_size.get(),
+            chain_head,
+            lag: chain_head.map(|head| (head + 1).saturating_sub(cursor)),
         }
     }
 
@@ -91,13 +190,321 @@ impl QueryRoot {
             .unwrap_or_default()
             .unwrap_or_default()
     }
+
+    /// A deposit and how far it has got. Deposits still collecting relayer attestations
+    /// show the first attested version.
+    async fn deposit(&self, nonce: u64) -> Option<DepositRecord> {
+        let state = &self.service.state;
+        if let Some(deposit) = state.deposits.get(&nonce).await.ok().flatten() {
+            return Some(self.deposit_record(deposit).await);
+        }
+        let attestations = state.attestations.get(&nonce).await.ok().flatten()?;
+        Some(DepositRecord {
+            deposit: attestations.first()?.deposit.clone(),
+            status: DepositStatus::AwaitingAttestations,
+        })
+    }
+
+    /// Recorded deposits, newest first
+    async fn deposits(&self, offset: Option<usize>, limit: Option<usize>) -> Vec<DepositRecord> {
+        let mut nonces = self.service.state.deposits.indices().await.unwrap_or_default();
+        nonces.sort_unstable_by(|a, b| b.cmp(a));
+        let mut records = Vec::new();
+        for nonce in nonces.into_iter().skip(offset.unwrap_or(0)).take(limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE)) {
+            if let Ok(Some(deposit)) = self.service.state.deposits.get(&nonce).await {
+                records.push(self.deposit_record(deposit).await);
+            }
+        }
+        records
+    }
+
+    async fn withdrawal(&self, nonce: u64) -> Option<WithdrawalRecord> {
+        let request = self.service.state.withdrawals.get(&nonce).await.ok().flatten()?;
+        Some(self.withdrawal_record(request).await)
+    }
+
+    /// Withdrawals, optionally only one account's, newest first
+    async fn withdrawals(
+        &self,
+        owner: Option<AccountOwner>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<WithdrawalRecord> {
+        let state = &self.service.state;
+        let mut nonces = state.withdrawals.indices().await.unwrap_or_default();
+        nonces.sort_unstable_by(|a, b| b.cmp(a));
+        let mut requests = Vec::new();
+        for nonce in nonces {
+            if let Ok(Some(request)) = state.withdrawals.get(&nonce).await {
+                if owner.is_none_or(|owner| request.owner == owner) {
+                    requests.push(request);
+                }
+            }
+        }
+        let mut records = Vec::new();
+        for request in requests.into_iter().skip(offset.unwrap_or(0)).take(limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE)) {
+            records.push(self.withdrawal_record(request).await);
+        }
+        records
+    }
+
+    /// The Linera owner an EVM address is linked to
+    async fn evm_link(&self, evm_address: String) -> Option<AccountOwner> {
+        let evm_address = evm::normalize_address(&evm_address)?;
+        self.service.state.evm_links.get(&evm_address).await.ok().flatten()
+    }
+
+    /// The message `owner` must have the EVM address's key sign to link them next
+    async fn link_message(&self, evm_address: String, owner: AccountOwner) -> Option<String> {
+        let evm_address = evm::normalize_address(&evm_address)?;
+        let bridge = self.service.runtime.application_id().forget_abi();
+        let nonce = self.service.state.link_nonces.get(&evm_address).await.ok()?.unwrap_or(0);
+        Some(link_message(&evm_address, &owner, &bridge, nonce))
+    }
+
+    /// Deposits from an EVM address that wait for it to be linked and claimed
+    async fn unclaimed_deposits(&self, evm_address: String) -> Vec<DepositEvent> {
+        let Some(evm_address) = evm::normalize_address(&evm_address) else {
+            return vec![];
+        };
+        let state = &self.service.state;
+        let nonces = state.unclaimed_deposits.get(&evm_address).await.ok().flatten().unwrap_or_default();
+        let mut deposits = Vec::new();
+        for nonce in nonces {
+            if let Ok(Some(deposit)) = state.deposits.get(&nonce).await {
+                deposits.push(deposit);
+            }
+        }
+        deposits
+    }
+
+    async fn relayers(&self) -> Vec<String> {
+        self.service.state.relayers.get().clone()
+    }
+
+    async fn attestation_threshold(&self) -> u32 {
+        *self.service.state.attestation_threshold.get()
+    }
+
+    /// Relayer attestations submitted for a deposit
+    async fn attestations(&self, nonce: u64) -> Vec<DepositAttestation> {
+        self.service.state.attestations.get(&nonce).await.ok().flatten().unwrap_or_default()
+    }
+
+    /// A withdrawal voucher ready to submit to the Vault, once enough relayers have signed it
+    async fn withdrawal_voucher(&self, nonce: u64) -> Option<WithdrawalVoucher> {
+        let state = &self.service.state;
+        let request = state.withdrawals.get(&nonce).await.ok().flatten()?;
+        if !request.processed || request.refunded {
+            return None;
+        }
+        let chain_id = *state.chain_id.get();
+        let vault = state.bridge_contract.get().clone();
+        let digest = request.voucher_digest(chain_id, &vault)?;
+        let relayers = state.relayers.get();
+        let mut signatures = request
+            .signatures
+            .into_iter()
+            .filter(|signed| relayers.contains(&signed.relayer))
+            .collect::<Vec<_>>();
+        signatures.sort_by(|a, b| a.relayer.cmp(&b.relayer));
+        Some(WithdrawalVoucher {
+            user: request.user,
+            token: request.token,
+            amount: request.amount,
+            nonce,
+            deadline: request.deadline,
+            chain_id,
+            vault,
+            digest: format!("0x{}", hex::encode(digest)),
+            signatures: signatures.into_iter().map(|signed| signed.signature).collect(),
+        })
+    }
+
+    async fn bridge_limits(&self, token: BridgeToken) -> BridgeLimits {
+        self.service.state.bridge_limits.get(&token).await.ok().flatten().unwrap_or_default()
+    }
+
+    /// How much of a token moved in one direction over the last 24 hours
+    async fn moved_today(&self, token: BridgeToken, direction: TransferDirection) -> Amount {
+        let state = &self.service.state;
+        let flows = match direction {
+            TransferDirection::Deposit => &state.minted_window,
+            TransferDirection::Withdrawal => &state.withdrawn_window,
+        };
+        let window = flows.get(&token).await.ok().flatten().unwrap_or_default();
+        window.total(self.service.runtime.system_time().micros())
+    }
+
+    /// Transfers held back by a limit, oldest first
+    async fn queued_transfers(&self) -> Vec<QueuedTransfer> {
+        let mut transfers = Vec::new();
+        let state = &self.service.state;
+        for key in state.queued_transfers.indices().await.unwrap_or_default() {
+            if let Ok(Some(transfer)) = state.queued_transfers.get(&key).await {
+                transfers.push(transfer);
+            }
+        }
+        transfers.sort_by_key(|transfer| transfer.queued_at);
+        transfers
+    }
+
+    async fn paused(&self, direction: TransferDirection) -> bool {
+        match direction {
+            TransferDirection::Deposit => *self.service.state.deposits_paused.get(),
+            TransferDirection::Withdrawal => *self.service.state.withdrawals_paused.get(),
+        }
+    }
+
+    /// Wrapped supply minted minus burned, per token
+    async fn supply(&self, token: BridgeToken) -> Amount {
+        self.service.state.supply.get(&token).await.ok().flatten().unwrap_or_default()
+    }
+
+    /// Compares each token's wrapped supply with what the Vault holds on this tracker's
+    /// network, read at the latest block. Unminted deposits and unredeemed withdrawals
+    /// show up as surplus; a deficit means supply exceeds collateral.
+    async fn solvency(&self) -> Vec<SolvencyReport> {
+        let state = &self.service.state;
+        let client = ServiceEthereumClient::new(state.ethereum_endpoint.get().clone());
+        let vault = state.bridge_contract.get().clone();
+        let block_number = client.get_block_number().await.ok();
+        let usdc_contract = state.usdc_contract.get().clone();
+        let assets = [
+            (BASE_TOKEN_SYMBOL, Some(usdc_contract), USDC_DECIMALS),
+            (WETH_SYMBOL, None, ETH_DECIMALS),
+        ];
+
+        let mut reports = Vec::new();
+        for (symbol, erc20, decimals) in assets {
+            let token = BridgeToken {
+                symbol: symbol.to_string(),
+                network: state.network.get().clone(),
+            };
+            let supply = state.supply.get(&token).await.ok().flatten().unwrap_or_default();
+            let held = match (block_number, erc20) {
+                (Some(block), Some(erc20)) => {
+                    let call = evm::parse_address(&vault).map(|vault| evm::balance_of_call(&vault));
+                    match call {
+                        Some(call) => client
+                            .non_executive_call(&erc20, call.into(), &vault, block)
+                            .await
+                            .ok()
+                            .and_then(|output| evm::decode_uint(&output))
+                            .map(|held| held.to_string()),
+                        None => None,
+                    }
+                }
+                (Some(block), None) => client.get_balance(&vault, block).await.ok().map(|held| held.to_string()),
+                (None, _) => None,
+            };
+            let collateral = held.and_then(|held| evm_amount(&held, decimals));
+            let (surplus, deficit) = collateral
+                .map_or((Amount::ZERO, Amount::ZERO), |collateral| collateral_gap(supply, collateral));
+            reports.push(SolvencyReport {
+                token,
+                supply,
+                collateral,
+                solvent: collateral.map(|collateral| collateral >= supply),
+                surplus,
+                deficit,
+                block_number,
+            });
+        }
+        reports
+    }
+
+    /// Deposits whose relayers disagree about what happened
+    async fn deposit_conflicts(&self) -> Vec<DepositConflict> {
+        let state = &self.service.state;
+        let mut conflicts = Vec::new();
+        for nonce in state.attestations.indices().await.unwrap_or_default() {
+            let Ok(Some(attestations)) = state.attestations.get(&nonce).await else {
+                continue;
+            };
+            let first = &attestations[0].deposit;
+            if attestations.iter().any(|attestation| attestation.deposit != *first) {
+                let minted = state.deposits.get(&nonce).await.ok().flatten();
+                conflicts.push(DepositConflict { nonce, minted, attestations });
+            }
+        }
+        conflicts
+    }
 }
 
-struct MutationRoot;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum DepositStatus {
+    /// Not yet agreed on by the threshold of current relayers
+    AwaitingAttestations,
+    /// Waiting for its EVM address to be linked and claimed
+    Unclaimed,
+    /// Held back by a bridge limit until the owner releases it
+    Queued,
+    Minted,
+    /// Not mintable: an unbridged token or an unreadable user address
+    Ignored,
+}
 
-#[async_graphql::Object]
-impl MutationRoot {
-    async fn placeholder(&self) -> bool {
-        true
+#[derive(SimpleObject)]
+struct DepositRecord {
+    deposit: DepositEvent,
+    status: DepositStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum WithdrawalStatus {
+    /// Held back by a bridge limit until the owner releases it
+    Queued,
+    AwaitingSignatures,
+    /// The voucher can be submitted to the Vault
+    Ready,
+    /// The voucher's deadline has passed; its owner can refund it with `RefundWithdrawal`
+    Expired,
+    /// Minted back to the owner after expiring unused
+    Refunded,
+}
+
+#[derive(SimpleObject)]
+struct WithdrawalRecord {
+    request: WithdrawalRequest,
+    status: WithdrawalStatus,
+}
+
+/// `cursor` is the next block to scan; `lag` counts the blocks up to the head left to scan
+#[derive(SimpleObject)]
+struct ScanProgress {
+    cursor: u64,
+    chunk_size: u64,
+    chain_head: Option<u64>,
+    lag: Option<u64>,
+}
+
+/// Wrapped supply against Vault collateral for one token. `collateral` and `solvent`
+/// are absent when the Vault could not be read.
+#[derive(SimpleObject)]
+struct SolvencyReport {
+    token: BridgeToken,
+    supply: Amount,
+    collateral: Option<Amount>,
+    solvent: Option<bool>,
+    surplus: Amount,
+    deficit: Amount,
+    block_number: Option<u64>,
+}
+
+/// Disagreeing attestations for one deposit nonce, and what was minted for it if anything
+#[derive(SimpleObject)]
+struct DepositConflict {
+    nonce: u64,
+    minted: Option<DepositEvent>,
+    attestations: Vec<DepositAttestation>,
+}
+
+/// Addresses and integers as the plain strings deposits are recorded with
+fn decode_value(value: &EthereumDataType) -> String {
+    match value {
+        EthereumDataType::Address(address) => address.clone(),
+        EthereumDataType::Uint256(value) => value.to_string(),
+        other => format!("{other:?}"),
     }
 }
@@ -1,4 +1,9 @@
-use bridge_tracker::{DepositEvent, WithdrawalRequest};
+use bridge_tracker::{
+    limits::{BridgeLimits, QueuedTransfer, RollingWindow, TransferDirection},
+    DepositAttestation, DepositEvent, WithdrawalRequest,
+};
+use dex::BridgeToken;
+use linera_sdk::linera_base_types::{AccountOwner, Amount, ApplicationId};
 use linera_sdk::views::{linera_views, MapView, RegisterView, RootView, ViewStorageContext};
 use serde::{Deserialize, Serialize};
 
@@ -9,7 +14,27 @@ pub struct BridgeTrackerState {
     pub bridge_contract: RegisterView<String>,
     pub usdc_contract: RegisterView<String>,
     pub start_block: RegisterView<u64>,
+    pub scan_cursor: RegisterView<u64>, // Next block to scan for deposits
+    pub scan_chunk_size: RegisterView<u64>,
+    pub chain_id: RegisterView<u64>,
+    pub network: RegisterView<String>,
+    pub dex_app: RegisterView<Option<ApplicationId>>,
+    pub owner: RegisterView<Option<AccountOwner>>,
     pub deposits: MapView<u64, DepositEvent>,
     pub withdrawals: MapView<u64, WithdrawalRequest>,
+    pub next_withdrawal_nonce: RegisterView<u64>,
     pub user_balances: MapView<(String, String), String>, // (user, token) -> balance
+    pub evm_links: MapView<String, AccountOwner>, // EVM address -> Linera owner
+    pub link_nonces: MapView<String, u64>, // EVM address -> links made so far
+    pub unclaimed_deposits: MapView<String, Vec<u64>>, // EVM address -> nonces of deposits awaiting a link
+    pub relayers: RegisterView<Vec<String>>,
+    pub attestation_threshold: RegisterView<u32>,
+    pub attestations: MapView<u64, Vec<DepositAttestation>>, // deposit nonce -> relayer attestations
+    pub bridge_limits: MapView<BridgeToken, BridgeLimits>,
+    pub minted_window: MapView<BridgeToken, RollingWindow>,
+    pub withdrawn_window: MapView<BridgeToken, RollingWindow>,
+    pub queued_transfers: MapView<(TransferDirection, u64), QueuedTransfer>,
+    pub deposits_paused: RegisterView<bool>,
+    pub withdrawals_paused: RegisterView<bool>,
+    pub supply: MapView<BridgeToken, Amount>, // Minted minus burned
 }
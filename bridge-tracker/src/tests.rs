@@ -0,0 +1,359 @@
+#[cfg(test)]
+mod tests {
+    use k256::ecdsa::SigningKey;
+    use linera_sdk::linera_base_types::{AccountOwner, Amount};
+
+    use crate::{evm, WithdrawalRequest, ETH_TOKEN, VAULT_DOMAIN_NAME, VAULT_DOMAIN_VERSION};
+
+    fn word(hex_digits: &str) -> [u8; 32] {
+        hex::decode(hex_digits).unwrap().try_into().unwrap()
+    }
+
+    /// A 65-byte `r || s || v` signature of `digest`, as wallets produce it
+    fn sign(key: &SigningKey, digest: &[u8; 32]) -> [u8; 65] {
+        let (signed, recovery_id) = key.sign_prehash_recoverable(digest).unwrap();
+        let mut signature = [0; 65];
+        signature[..64].copy_from_slice(&signed.to_bytes());
+        signature[64] = recovery_id.to_byte() + 27;
+        signature
+    }
+
+    #[test]
+    fn test_eip712_mail_vector() {
+        // The `Mail` example from the EIP-712 specification
+        let cow = evm::parse_address("0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826").unwrap();
+        let bob = evm::parse_address("0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB").unwrap();
+        let verifying_contract = evm::parse_address("0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC").unwrap();
+        let domain = evm::domain_separator("Ether Mail", "1", 1, &verifying_contract);
+        assert_eq!(domain, word("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"));
+
+        let person = |name: &str, wallet| {
+            evm::hash_words(&[
+                evm::keccak256(b"Person(string name,address wallet)"),
+                evm::keccak256(name.as_bytes()),
+                evm::address_word(wallet),
+            ])
+        };
+        let mail = evm::hash_words(&[
+            evm::keccak256(b"Mail(Person from,Person to,string contents)Person(string name,address wallet)"),
+            person("Cow", &cow),
+            person("Bob", &bob),
+            evm::keccak256(b"Hello, Bob!"),
+        ]);
+        assert_eq!(mail, word("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"));
+        let digest = evm::typed_data_hash(&domain, &mail);
+        assert_eq!(digest, word("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"));
+
+        let signature = evm::parse_signature(
+            "0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+             07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562\
+             1c",
+        )
+        .unwrap();
+        assert_eq!(evm::recover_signer(&digest, &signature), Some(cow));
+    }
+
+    #[test]
+    fn test_recover_known_key() {
+        // web3.js `personal_sign` example for key 0x4c08...2318
+        let key = SigningKey::from_slice(&word("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")).unwrap();
+        let address = evm::parse_address("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23").unwrap();
+        let digest = evm::personal_message_hash(b"Some data");
+        let signature = evm::parse_signature(
+            "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd\
+             6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a029\
+             1c",
+        )
+        .unwrap();
+        assert_eq!(evm::recover_signer(&digest, &signature), Some(address));
+
+        // A fresh signature from the same key recovers it too, with either `v` encoding
+        let (signed, recovery_id) = key.sign_prehash_recoverable(&digest).unwrap();
+        let mut fresh = [0; 65];
+        fresh[..64].copy_from_slice(&signed.to_bytes());
+        fresh[64] = recovery_id.to_byte();
+        assert_eq!(evm::recover_signer(&digest, &fresh), Some(address));
+        fresh[64] += 27;
+        assert_eq!(evm::recover_signer(&digest, &fresh), Some(address));
+
+        // The high-s twin of a valid signature is rejected, as is a different message
+        let high_s = signed.s().negate();
+        let twin = k256::ecdsa::Signature::from_scalars(signed.r().to_bytes(), high_s.to_bytes()).unwrap();
+        fresh[32..64].copy_from_slice(&twin.s().to_bytes());
+        fresh[64] = (recovery_id.to_byte() ^ 1) + 27;
+        assert_eq!(evm::recover_signer(&digest, &fresh), None);
+        assert_ne!(evm::recover_signer(&evm::personal_message_hash(b"Other data"), &signature), Some(address));
+    }
+
+    #[test]
+    fn test_voucher_digest() {
+        let key = SigningKey::from_slice(&word("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")).unwrap();
+        let relayer = evm::parse_address("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23").unwrap();
+        let vault = "0x7449478525Eb5106f487d44672B40592Af2a4E49";
+        let user = "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826";
+        let request = WithdrawalRequest {
+            user: user.to_string(),
+            token: ETH_TOKEN.to_string(),
+            amount: "1500000000000000000".to_string(),
+            nonce: 7,
+            processed: false,
+            owner: AccountOwner::Address20([1; 20]),
+            deadline: 1_700_000_000,
+            signatures: Vec::new(),
+            refunded: false,
+        };
+
+        // Matches the struct the Vault hashes in `_useVoucher`
+        let domain = evm::domain_separator(VAULT_DOMAIN_NAME, VAULT_DOMAIN_VERSION, 11155111, &evm::parse_address(vault).unwrap());
+        let withdrawal = evm::hash_words(&[
+            evm::keccak256(b"Withdrawal(address user,address token,uint256 amount,uint256 nonce,uint256 deadline)"),
+            evm::address_word(&evm::parse_address(user).unwrap()),
+            [0; 32],
+            evm::uint_word(1_500_000_000_000_000_000),
+            evm::uint_word(7),
+            evm::uint_word(1_700_000_000),
+        ]);
+        let digest = request.voucher_digest(11155111, vault).unwrap();
+        assert_eq!(digest, evm::typed_data_hash(&domain, &withdrawal));
+        assert_ne!(request.voucher_digest(1, vault), Some(digest));
+
+        assert_eq!(evm::recover_signer(&digest, &sign(&key, &digest)), Some(relayer));
+    }
+
+    #[test]
+    fn test_rolling_window_boundary() {
+        use crate::limits::{RollingWindow, WINDOW_MICROS};
+
+        let mut window = RollingWindow::default();
+        window.record(10, Amount::from_tokens(5));
+        window.record(WINDOW_MICROS, Amount::from_tokens(7));
+        assert_eq!(window.total(WINDOW_MICROS), Amount::from_tokens(12));
+        // An entry stops counting exactly 24 hours after it was recorded
+        assert_eq!(window.total(WINDOW_MICROS + 9), Amount::from_tokens(12));
+        assert_eq!(window.total(WINDOW_MICROS + 10), Amount::from_tokens(7));
+        assert_eq!(window.total(2 * WINDOW_MICROS), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_single_deposit_cap() {
+        use crate::limits::{BridgeLimits, TransferDirection};
+
+        let limits = BridgeLimits { max_deposit: Some(Amount::from_tokens(100)), ..BridgeLimits::default() };
+        assert!(limits.allows(TransferDirection::Deposit, Amount::from_tokens(100), Amount::from_tokens(1_000)));
+        assert!(!limits.allows(TransferDirection::Deposit, Amount::from_tokens(101), Amount::ZERO));
+        // The single-transfer cap only applies to deposits
+        assert!(limits.allows(TransferDirection::Withdrawal, Amount::from_tokens(101), Amount::ZERO));
+        assert!(BridgeLimits::default().allows(TransferDirection::Deposit, Amount::from_tokens(1_000_000), Amount::MAX));
+    }
+
+    #[test]
+    fn test_daily_cap_queues_excess() {
+        use crate::limits::{BridgeLimits, RollingWindow, TransferDirection, WINDOW_MICROS};
+
+        let deposit = TransferDirection::Deposit;
+        let limits = BridgeLimits {
+            daily_mint_cap: Some(Amount::from_tokens(150)),
+            daily_withdrawal_cap: Some(Amount::from_tokens(10)),
+            ..BridgeLimits::default()
+        };
+        let mut window = RollingWindow::default();
+        assert!(window.admit(&limits, deposit, 1, Amount::from_tokens(100)));
+        // Over the cap: left for the caller to queue and not counted
+        assert!(!window.admit(&limits, deposit, 2, Amount::from_tokens(60)));
+        assert_eq!(window.total(2), Amount::from_tokens(100));
+        assert!(window.admit(&limits, deposit, 3, Amount::from_tokens(50)));
+        assert!(!window.admit(&limits, deposit, 4, Amount::from_attos(1)));
+
+        // Once the first deposit leaves the window there is room again
+        assert!(!window.admit(&limits, deposit, WINDOW_MICROS, Amount::from_tokens(60)));
+        assert!(window.admit(&limits, deposit, WINDOW_MICROS + 1, Amount::from_tokens(60)));
+        assert_eq!(window.total(WINDOW_MICROS + 1), Amount::from_tokens(110));
+
+        // Withdrawals have their own cap
+        let mut withdrawn = RollingWindow::default();
+        assert!(!withdrawn.admit(&limits, TransferDirection::Withdrawal, 0, Amount::from_tokens(11)));
+        assert!(withdrawn.admit(&limits, TransferDirection::Withdrawal, 0, Amount::from_tokens(10)));
+    }
+
+    #[test]
+    fn test_link_signature() {
+        use linera_sdk::linera_base_types::{ApplicationId, CryptoHash};
+
+        use crate::{link_message, link_signer};
+
+        let key = SigningKey::from_slice(&word("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")).unwrap();
+        let other_key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let address = evm::parse_address("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23").unwrap();
+        let evm_address = evm::format_address(&address);
+        let owner = AccountOwner::Address20([1; 20]);
+        let bridge = ApplicationId::new(CryptoHash::from([3; 4]));
+        let signed = |key: &SigningKey, owner: &AccountOwner, nonce| {
+            sign(key, &evm::personal_message_hash(link_message(&evm_address, owner, &bridge, nonce).as_bytes()))
+        };
+
+        let first = signed(&key, &owner, 0);
+        assert_eq!(link_signer(&evm_address, &owner, &bridge, 0, &first), Some(address));
+
+        // Another key, or a signature for another owner or bridge, does not prove the address
+        let forged = signed(&other_key, &owner, 0);
+        assert_ne!(link_signer(&evm_address, &owner, &bridge, 0, &forged), Some(address));
+        let other_owner = AccountOwner::Address20([2; 20]);
+        assert_ne!(link_signer(&evm_address, &other_owner, &bridge, 0, &first), Some(address));
+        let other_bridge = ApplicationId::new(CryptoHash::from([4; 4]));
+        assert_ne!(link_signer(&evm_address, &owner, &other_bridge, 0, &first), Some(address));
+
+        // Once the link is made the nonce moves on, so the old signature cannot relink
+        assert_ne!(link_signer(&evm_address, &owner, &bridge, 1, &first), Some(address));
+        let second = signed(&key, &owner, 1);
+        assert_eq!(link_signer(&evm_address, &owner, &bridge, 1, &second), Some(address));
+    }
+
+    #[test]
+    fn test_claim_held_deposits() {
+        use crate::claim_held_deposits;
+
+        let owner = AccountOwner::Address20([1; 20]);
+        // Deposits stay held while the address is unlinked
+        assert_eq!(claim_held_deposits(None, Some(vec![3, 5])), None);
+        assert_eq!(claim_held_deposits(None, None), None);
+        // Once linked, every held deposit is released to the owner
+        assert_eq!(claim_held_deposits(Some(owner), Some(vec![3, 5])), Some((owner, vec![3, 5])));
+        assert_eq!(claim_held_deposits(Some(owner), None), Some((owner, vec![])));
+    }
+
+    #[test]
+    fn test_deposit_attestations() {
+        use crate::{add_attestation, attested_deposit, AttestationError, DepositAttestation, DepositEvent};
+
+        let relayers = ["0x01", "0x02", "0x03"].map(String::from).to_vec();
+        let deposit = |amount: &str| DepositEvent {
+            user: "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826".to_string(),
+            token: ETH_TOKEN.to_string(),
+            amount: amount.to_string(),
+            nonce: 4,
+            block_number: 100,
+        };
+        let attest = |attestations: &mut Vec<DepositAttestation>, relayers: &[String], relayer: &str, amount| {
+            add_attestation(attestations, relayers, DepositAttestation { relayer: relayer.to_string(), deposit: deposit(amount) })
+        };
+
+        // The deposit is minted once two of the three relayers agree on it
+        let mut attestations = Vec::new();
+        attest(&mut attestations, &relayers, "0x01", "5").unwrap();
+        assert_eq!(attested_deposit(&attestations, &relayers, 2), None);
+        assert_eq!(attest(&mut attestations, &relayers, "0x01", "5"), Err(AttestationError::AlreadyAttested));
+        assert_eq!(attest(&mut attestations, &relayers, "0x04", "5"), Err(AttestationError::NotRelayer));
+        assert_eq!(attested_deposit(&attestations, &relayers, 2), None);
+        attest(&mut attestations, &relayers, "0x02", "5").unwrap();
+        assert_eq!(attested_deposit(&attestations, &relayers, 2), Some(&deposit("5")));
+
+        // Disagreeing versions don't add up
+        let mut conflicting = Vec::new();
+        attest(&mut conflicting, &relayers, "0x01", "5").unwrap();
+        attest(&mut conflicting, &relayers, "0x02", "6").unwrap();
+        attest(&mut conflicting, &relayers, "0x03", "7").unwrap();
+        assert_eq!(attested_deposit(&conflicting, &relayers, 2), None);
+        // Nor is either side minted when two versions both meet the threshold
+        assert_eq!(attested_deposit(&conflicting, &relayers, 1), None);
+
+        // Re-tallied after the relayer set changes: a removed relayer's attestation no
+        // longer counts, and dropping the dissenter lets the agreed version through
+        let remaining = ["0x02", "0x03"].map(String::from).to_vec();
+        assert_eq!(attested_deposit(&attestations, &remaining, 2), None);
+        assert_eq!(attested_deposit(&attestations, &remaining, 1), Some(&deposit("5")));
+        assert_eq!(attested_deposit(&conflicting, &remaining[..1], 1), Some(&deposit("6")));
+        assert_eq!(attested_deposit(&attestations, &relayers, 0), None);
+    }
+
+    #[test]
+    fn test_refund_eligibility() {
+        use crate::{RefundError, REFUND_GRACE_SECS};
+
+        let owner = AccountOwner::Address20([1; 20]);
+        let mut request = WithdrawalRequest {
+            user: "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826".to_string(),
+            token: ETH_TOKEN.to_string(),
+            amount: "1000".to_string(),
+            nonce: 2,
+            processed: true,
+            owner,
+            deadline: 1_000,
+            signatures: Vec::new(),
+            refunded: false,
+        };
+        let expired = 1_000 + REFUND_GRACE_SECS + 1;
+
+        // Nothing is refunded until the grace period after the deadline has passed
+        assert_eq!(request.check_refund(Some(owner), false, 1_000), Err(RefundError::NotExpired));
+        assert_eq!(request.check_refund(Some(owner), false, 1_000 + REFUND_GRACE_SECS), Err(RefundError::NotExpired));
+        request.check_refund(Some(owner), false, expired).unwrap();
+
+        // Only the account the tokens were burned from, and never a queued withdrawal
+        let stranger = AccountOwner::Address20([2; 20]);
+        assert_eq!(request.check_refund(Some(stranger), false, expired), Err(RefundError::NotOwner));
+        assert_eq!(request.check_refund(None, false, expired), Err(RefundError::NotOwner));
+        assert_eq!(request.check_refund(Some(owner), true, expired), Err(RefundError::Queued));
+
+        request.refunded = true;
+        assert_eq!(request.check_refund(Some(owner), false, expired), Err(RefundError::AlreadyRefunded));
+    }
+
+    #[test]
+    fn test_collateral_gap() {
+        use crate::{collateral_gap, evm_amount, USDC_DECIMALS};
+
+        let supply = Amount::from_tokens(1_000);
+        // The Vault's raw USDC balance, 1,000.25 at six decimals
+        let collateral = evm_amount("1000250000", USDC_DECIMALS).unwrap();
+        assert_eq!(collateral_gap(supply, collateral), (Amount::from_attos(250_000_000_000_000_000), Amount::ZERO));
+        assert_eq!(collateral_gap(collateral, supply), (Amount::ZERO, Amount::from_attos(250_000_000_000_000_000)));
+        assert_eq!(collateral_gap(supply, supply), (Amount::ZERO, Amount::ZERO));
+        assert_eq!(collateral_gap(Amount::ZERO, collateral), (collateral, Amount::ZERO));
+        assert_eq!(collateral_gap(supply, Amount::ZERO), (Amount::ZERO, supply));
+    }
+
+    #[test]
+    fn test_bridged_asset_amounts() {
+        use crate::{bridged_asset, evm_amount, ETH_DECIMALS, USDC_DECIMALS, WETH_SYMBOL};
+
+        let usdc = "0x1c7D4B196Cb0C7B01d743Fbc6116a902379C7238";
+        assert_eq!(bridged_asset(ETH_TOKEN, usdc), Some((WETH_SYMBOL, ETH_DECIMALS)));
+        assert_eq!(bridged_asset(&usdc.to_lowercase(), usdc), Some((dex::BASE_TOKEN_SYMBOL, USDC_DECIMALS)));
+        assert_eq!(bridged_asset("0x7449478525Eb5106f487d44672B40592Af2a4E49", usdc), None);
+        assert_eq!(bridged_asset("not an address", usdc), None);
+
+        // Wei already carries 18 decimals, so it maps one to one onto attos
+        assert_eq!(evm_amount("1500000000000000000", ETH_DECIMALS), Some(Amount::from_attos(1_500_000_000_000_000_000)));
+        assert_eq!(evm_amount("1", ETH_DECIMALS), Some(Amount::from_attos(1)));
+        assert_eq!(evm_amount("2500000", USDC_DECIMALS), Some(Amount::from_attos(2_500_000_000_000_000_000)));
+        assert_eq!(evm_amount(&u128::MAX.to_string(), ETH_DECIMALS), Some(Amount::from_attos(u128::MAX)));
+        // Amounts that overflow once scaled, have more decimals than `Amount`, or aren't integers
+        assert_eq!(evm_amount(&u128::MAX.to_string(), USDC_DECIMALS), None);
+        assert_eq!(evm_amount("1", 19), None);
+        assert_eq!(evm_amount("1.5", ETH_DECIMALS), None);
+        assert_eq!(evm_amount("-1", ETH_DECIMALS), None);
+    }
+
+    #[test]
+    fn test_scan_chunks() {
+        use crate::{scan_chunks, MAX_SCAN_CHUNKS};
+
+        // The last chunk stops at `end_block`, which falls inside it
+        assert_eq!(scan_chunks(100, 4_550, 2_000), [(100, 2_100), (2_100, 4_100), (4_100, 4_550)]);
+        // An `end_block` on a chunk boundary leaves no empty trailing chunk
+        assert_eq!(scan_chunks(0, 4_000, 2_000), [(0, 2_000), (2_000, 4_000)]);
+        assert_eq!(scan_chunks(10, 11, 2_000), [(10, 11)]);
+        // Nothing to scan once the cursor has reached `end_block`
+        assert!(scan_chunks(500, 500, 2_000).is_empty());
+        assert!(scan_chunks(500, 400, 2_000).is_empty());
+
+        // A long range is covered over several calls, each resuming where the last stopped
+        let chunks = scan_chunks(0, 1_000_000, 1_000);
+        assert_eq!(chunks.len() as u64, MAX_SCAN_CHUNKS);
+        assert!(chunks.windows(2).all(|pair| pair[0].1 == pair[1].0));
+        let cursor = chunks.last().unwrap().1;
+        assert_eq!(cursor, MAX_SCAN_CHUNKS * 1_000);
+        assert_eq!(scan_chunks(cursor, 1_000_000, 1_000)[0], (cursor, cursor + 1_000));
+        assert_eq!(scan_chunks(u64::MAX - 5, u64::MAX, 2_000), [(u64::MAX - 5, u64::MAX)]);
+    }
+}
@@ -0,0 +1,165 @@
+use async_graphql::{Enum, SimpleObject};
+use linera_sdk::linera_base_types::{AccountOwner, ApplicationId};
+use serde::{Deserialize, Serialize};
+
+use crate::{math, BridgeToken, DexError, DexState, NATIVE_NETWORK};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Enum)]
+pub enum AdminActionKind {
+    AddAdmin,
+    RemoveAdmin,
+    RenounceAdmin,
+    TransferOwnership,
+    RenounceOwnership,
+    SetPoolFee,
+    SetPaused,
+    FundRewardProgram,
+    SetBridgeTracker,
+    SetTrustedMinter,
+}
+
+// An entry in the DEX's audit log of privileged actions
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct AdminAction {
+    pub timestamp: u64,
+    pub actor: AccountOwner,
+    pub action: AdminActionKind,
+    pub detail: String,
+}
+
+impl DexState {
+    pub fn require_owner(&self, user: &AccountOwner) -> Result<(), DexError> {
+        if self.owner.as_ref() != Some(user) {
+            return Err(DexError::NotOwner);
+        }
+        Ok(())
+    }
+
+    /// The owner counts as an admin
+    pub fn require_admin(&self, user: &AccountOwner) -> Result<(), DexError> {
+        if self.owner.as_ref() != Some(user) && !self.admins.contains(user) {
+            return Err(DexError::NotAdmin);
+        }
+        Ok(())
+    }
+
+    /// Appends an entry to the audit log and returns it for the caller to publish
+    pub fn log_admin_action(
+        &mut self,
+        now: u64,
+        actor: AccountOwner,
+        action: AdminActionKind,
+        detail: String,
+    ) -> AdminAction {
+        let entry = AdminAction {
+            timestamp: now,
+            actor,
+            action,
+            detail,
+        };
+        self.admin_log.push(entry.clone());
+        entry
+    }
+
+    pub fn add_admin(&mut self, user: AccountOwner, admin: AccountOwner, now: u64) -> Result<AdminAction, DexError> {
+        self.require_owner(&user)?;
+        self.admins.insert(admin);
+        Ok(self.log_admin_action(now, user, AdminActionKind::AddAdmin, admin.to_string()))
+    }
+
+    pub fn remove_admin(&mut self, user: AccountOwner, admin: AccountOwner, now: u64) -> Result<AdminAction, DexError> {
+        self.require_owner(&user)?;
+        if !self.admins.remove(&admin) {
+            return Err(DexError::NotAdmin);
+        }
+        Ok(self.log_admin_action(now, user, AdminActionKind::RemoveAdmin, admin.to_string()))
+    }
+
+    pub fn renounce_admin(&mut self, user: AccountOwner, now: u64) -> Result<AdminAction, DexError> {
+        if !self.admins.remove(&user) {
+            return Err(DexError::NotAdmin);
+        }
+        Ok(self.log_admin_action(now, user, AdminActionKind::RenounceAdmin, user.to_string()))
+    }
+
+    pub fn transfer_ownership(
+        &mut self,
+        user: AccountOwner,
+        new_owner: AccountOwner,
+        now: u64,
+    ) -> Result<AdminAction, DexError> {
+        self.require_owner(&user)?;
+        self.owner = Some(new_owner);
+        Ok(self.log_admin_action(now, user, AdminActionKind::TransferOwnership, new_owner.to_string()))
+    }
+
+    pub fn renounce_ownership(&mut self, user: AccountOwner, now: u64) -> Result<AdminAction, DexError> {
+        self.require_owner(&user)?;
+        self.owner = None;
+        Ok(self.log_admin_action(now, user, AdminActionKind::RenounceOwnership, user.to_string()))
+    }
+
+    pub fn set_pool_fee(
+        &mut self,
+        user: AccountOwner,
+        token_a: &BridgeToken,
+        token_b: &BridgeToken,
+        fee_rate: u32,
+        now: u64,
+    ) -> Result<AdminAction, DexError> {
+        self.require_admin(&user)?;
+        let pool_key = self.find_pool_key(token_a, token_b).ok_or(DexError::PoolNotFound)?;
+        if !math::is_valid_fee_rate(fee_rate) {
+            return Err(DexError::InvalidCalculation);
+        }
+        let pool = self.pools.get_mut(&pool_key).expect("pool key found above");
+        let old_fee_rate = std::mem::replace(&mut pool.fee_rate, fee_rate);
+        Ok(self.log_admin_action(
+            now,
+            user,
+            AdminActionKind::SetPoolFee,
+            format!("{}/{}: {old_fee_rate} -> {fee_rate} bps", pool_key.0.symbol, pool_key.1.symbol),
+        ))
+    }
+
+    pub fn set_paused(&mut self, user: AccountOwner, paused: bool, now: u64) -> Result<AdminAction, DexError> {
+        self.require_admin(&user)?;
+        self.paused = paused;
+        Ok(self.log_admin_action(now, user, AdminActionKind::SetPaused, paused.to_string()))
+    }
+
+    pub fn set_bridge_tracker_app(
+        &mut self,
+        user: AccountOwner,
+        app: Option<ApplicationId>,
+        now: u64,
+    ) -> Result<AdminAction, DexError> {
+        self.require_admin(&user)?;
+        let previous = std::mem::replace(&mut self.bridge_tracker_app, app);
+        Ok(self.log_admin_action(now, user, AdminActionKind::SetBridgeTracker, format!("{previous:?} -> {app:?}")))
+    }
+
+    /// Assigns or, with `None`, revokes the minter of one bridged network
+    pub fn set_trusted_minter(
+        &mut self,
+        user: AccountOwner,
+        network: String,
+        minter: Option<ApplicationId>,
+        now: u64,
+    ) -> Result<AdminAction, DexError> {
+        self.require_admin(&user)?;
+        if network.is_empty() || network == NATIVE_NETWORK {
+            return Err(DexError::InvalidTokenMetadata);
+        }
+        let previous = match minter {
+            Some(minter) => self.trusted_minters.insert(network.clone(), minter),
+            None => self.trusted_minters.remove(&network),
+        };
+        Ok(self.log_admin_action(
+            now,
+            user,
+            AdminActionKind::SetTrustedMinter,
+            format!("{network}: {previous:?} -> {minter:?}"),
+        ))
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::BTreeMap;
+
+use async_graphql::{ComplexObject, SimpleObject};
+use linera_sdk::linera_base_types::{AccountOwner, Amount};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    math::{is_valid_fee_rate, mul_div, swap_fee, FEE_DENOMINATOR},
+    oracle::PRICE_SCALE,
+    BridgeToken,
+};
+
+/// Fixed-point scale of square-root prices and fee growth (Q64.64)
+pub const Q64: u128 = 1 << 64;
+pub const MIN_TICK: i32 = -400_000;
+pub const MAX_TICK: i32 = 400_000;
+/// Upper bound on tick crossings within a single swap
+const MAX_SWAP_STEPS: usize = 256;
+
+/// sqrt(1.0001)^(2^i) in Q64.64, for each bit of a tick index
+const SQRT_RATIO_POWERS: [u128; 19] = [
+    18447666387855959850,
+    18448588748116922571,
+    18450433606991734263,
+    18454123878217468680,
+    18461506635090006701,
+    18476281010653910144,
+    18505865242158250041,
+    18565175891880433522,
+    18684368066214940582,
+    18925053041275764671,
+    19415764168677886926,
+    20435687552633177494,
+    22639080592224303007,
+    27784196929998399742,
+    41848122137994986128,
+    94936283578220370716,
+    488590176327622479860,
+    12941056668319229769860,
+    9078618265828848800676189,
+];
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeError {
+    #[error("Invalid tick range")]
+    InvalidRange,
+    #[error("Price out of range")]
+    InvalidPrice,
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity,
+    #[error("Calculation overflow")]
+    Overflow,
+    #[error("Pool tokens must differ")]
+    IdenticalTokens,
+}
+
+/// Square root of the price at `tick` (1.0001^tick), in Q64.64
+pub fn sqrt_price_at_tick(tick: i32) -> Option<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let magnitude = tick.unsigned_abs();
+    let mut ratio = Q64;
+    for (bit, power) in SQRT_RATIO_POWERS.iter().enumerate() {
+        if magnitude & (1 << bit) != 0 {
+            ratio = mul_div(ratio, *power, Q64)?;
+        }
+    }
+    if tick < 0 {
+        ratio = mul_div(Q64, Q64, ratio)?;
+    }
+    Some(ratio)
+}
+
+/// Largest tick whose square-root price does not exceed `sqrt_price`
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> i32 {
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let middle = low + (high - low + 1) / 2;
+        match sqrt_price_at_tick(middle) {
+            Some(ratio) if ratio <= sqrt_price => low = middle,
+            _ => high = middle - 1,
+        }
+    }
+    low
+}
+
+/// Square-root price for a price of token_a in token_b given as an `Amount`
+pub fn sqrt_price_from_price(price: Amount) -> Option<u128> {
+    // sqrt(price / PRICE_SCALE) * 2^64 == sqrt(price * 4^k / PRICE_SCALE) * 2^(64 - k);
+    // the largest `k` that fits keeps the most precision
+    (0..64u32).rev().find_map(|k| {
+        let scaled = mul_div(price.to_attos(), 1 << (2 * k), PRICE_SCALE)?;
+        integer_sqrt(scaled).checked_mul(1 << (64 - k))
+    })
+}
+
+/// Price of token_a in token_b for a square-root price
+pub fn price_from_sqrt_price(sqrt_price: u128) -> Amount {
+    let price = mul_div(sqrt_price, sqrt_price, Q64)
+        .and_then(|squared| mul_div(squared, PRICE_SCALE, Q64))
+        .unwrap_or(u128::MAX);
+    Amount::from_attos(price)
+}
+
+fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = 1u128 << ((128 - value.leading_zeros()).div_ceil(2));
+    loop {
+        let next = (x + value / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+/// token_a needed to move liquidity `L` between two square-root prices:
+/// L * (upper - lower) / (upper * lower)
+fn delta_a(lower: u128, upper: u128, liquidity: u128, round_up: bool) -> Option<u128> {
+    if liquidity == 0 || upper <= lower {
+        return Some(0);
+    }
+    let amount = mul_div(mul_div(liquidity, upper - lower, upper)?, Q64, lower)?;
+    amount.checked_add(u128::from(round_up))
+}
+
+/// token_b needed to move liquidity `L` between two square-root prices: L * (upper - lower)
+fn delta_b(lower: u128, upper: u128, liquidity: u128, round_up: bool) -> Option<u128> {
+    if liquidity == 0 || upper <= lower {
+        return Some(0);
+    }
+    let amount = mul_div(liquidity, upper - lower, Q64)?;
+    amount.checked_add(u128::from(round_up))
+}
+
+/// Gross input, including the fee, whose net after fees is `net`
+fn gross_up(net: u128, fee_rate: u32) -> Option<u128> {
+    let fee_rate = u128::from(fee_rate);
+    let denominator = FEE_DENOMINATOR.checked_sub(fee_rate).filter(|d| *d > 0)?;
+    let gross = mul_div(net, FEE_DENOMINATOR, denominator)?;
+    gross.checked_add(u128::from(fee_rate > 0))
+}
+
+// Per-tick bookkeeping; outside fee growth is relative to the current tick
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Tick {
+    pub liquidity_gross: u128,
+    pub liquidity_net: i128,
+    pub fee_growth_outside_a: u128,
+    pub fee_growth_outside_b: u128,
+}
+
+// A pool where liquidity is provided over tick ranges
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+#[graphql(complex)]
+pub struct ConcentratedPool {
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub fee_rate: u32,
+    pub tick_spacing: u32,
+    pub current_tick: i32,
+    pub liquidity: Amount, // Active liquidity, in sqrt(token_a * token_b) units
+    #[graphql(skip)]
+    pub sqrt_price: u128,
+    #[graphql(skip)]
+    pub fee_growth_global_a: u128,
+    #[graphql(skip)]
+    pub fee_growth_global_b: u128,
+    #[graphql(skip)]
+    pub ticks: BTreeMap<i32, Tick>,
+}
+
+// A liquidity position over a tick range, transferable by ID
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct Position {
+    pub id: u64,
+    pub owner: AccountOwner,
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: Amount,
+    pub tokens_owed_a: Amount, // Fees accrued and not yet collected
+    pub tokens_owed_b: Amount,
+    #[graphql(skip)]
+    pub fee_growth_inside_last_a: u128,
+    #[graphql(skip)]
+    pub fee_growth_inside_last_b: u128,
+}
+
+/// Result of a swap against a concentrated pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwapOutcome {
+    pub amount_in: u128,
+    pub amount_out: u128,
+    pub fee: u128,
+}
+
+#[ComplexObject]
+impl ConcentratedPool {
+    /// Current price of token_a in token_b
+    async fn price(&self) -> Amount {
+        price_from_sqrt_price(self.sqrt_price)
+    }
+}
+
+impl ConcentratedPool {
+    pub fn new(
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        fee_rate: u32,
+        tick_spacing: u32,
+        sqrt_price: u128,
+    ) -> Result<Self, RangeError> {
+        if token_a == token_b {
+            return Err(RangeError::IdenticalTokens);
+        }
+        let min = sqrt_price_at_tick(MIN_TICK).ok_or(RangeError::InvalidPrice)?;
+        let max = sqrt_price_at_tick(MAX_TICK).ok_or(RangeError::InvalidPrice)?;
+        if !(min..max).contains(&sqrt_price) {
+            return Err(RangeError::InvalidPrice);
+        }
+        if tick_spacing == 0 || tick_spacing > MAX_TICK as u32 || !is_valid_fee_rate(fee_rate) {
+            return Err(RangeError::InvalidRange);
+        }
+        Ok(ConcentratedPool {
+            token_a,
+            token_b,
+            fee_rate,
+            tick_spacing,
+            current_tick: tick_at_sqrt_price(sqrt_price),
+            liquidity: Amount::ZERO,
+            sqrt_price,
+            fee_growth_global_a: 0,
+            fee_growth_global_b: 0,
+            ticks: BTreeMap::new(),
+        })
+    }
+
+    pub fn check_range(&self, tick_lower: i32, tick_upper: i32) -> Result<(u128, u128), RangeError> {
+        let spacing = self.tick_spacing as i32;
+        if tick_lower >= tick_upper || tick_lower % spacing != 0 || tick_upper % spacing != 0 {
+            return Err(RangeError::InvalidRange);
+        }
+        let lower = sqrt_price_at_tick(tick_lower).ok_or(RangeError::InvalidRange)?;
+        let upper = sqrt_price_at_tick(tick_upper).ok_or(RangeError::InvalidRange)?;
+        Ok((lower, upper))
+    }
+
+    /// Token amounts represented by `liquidity` over a range at the current price
+    pub fn amounts_for_liquidity(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        round_up: bool,
+    ) -> Result<(u128, u128), RangeError> {
+        let (lower, upper) = self.check_range(tick_lower, tick_upper)?;
+        let price = self.sqrt_price.clamp(lower, upper);
+        let amount_a = delta_a(price, upper, liquidity, round_up).ok_or(RangeError::Overflow)?;
+        let amount_b = delta_b(lower, price, liquidity, round_up).ok_or(RangeError::Overflow)?;
+        Ok((amount_a, amount_b))
+    }
+
+    /// Largest liquidity over a range that the given amounts can fund
+    pub fn liquidity_for_amounts(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount_a: u128,
+        amount_b: u128,
+    ) -> Result<u128, RangeError> {
+        let (lower, upper) = self.check_range(tick_lower, tick_upper)?;
+        let price = self.sqrt_price.clamp(lower, upper);
+        let from_a = (price < upper)
+            .then(|| mul_div(mul_div(amount_a, price, Q64)?, upper, upper - price))
+            .map(|liquidity| liquidity.ok_or(RangeError::Overflow))
+            .transpose()?;
+        let from_b = (price > lower)
+            .then(|| mul_div(amount_b, Q64, price - lower))
+            .map(|liquidity| liquidity.ok_or(RangeError::Overflow))
+            .transpose()?;
+        Ok(match (from_a, from_b) {
+            (Some(from_a), Some(from_b)) => from_a.min(from_b),
+            (Some(liquidity), None) | (None, Some(liquidity)) => liquidity,
+            (None, None) => 0,
+        })
+    }
+
+    /// Fee growth per unit of liquidity earned inside a range so far
+    pub fn fee_growth_inside(&self, tick_lower: i32, tick_upper: i32) -> (u128, u128) {
+        let outside = |tick: i32| {
+            self.ticks
+                .get(&tick)
+                .map(|tick| (tick.fee_growth_outside_a, tick.fee_growth_outside_b))
+                .unwrap_or_default()
+        };
+        let (lower_a, lower_b) = outside(tick_lower);
+        let (upper_a, upper_b) = outside(tick_upper);
+        let (global_a, global_b) = (self.fee_growth_global_a, self.fee_growth_global_b);
+
+        let (below_a, below_b) = if self.current_tick >= tick_lower {
+            (lower_a, lower_b)
+        } else {
+            (global_a.wrapping_sub(lower_a), global_b.wrapping_sub(lower_b))
+        };
+        let (above_a, above_b) = if self.current_tick < tick_upper {
+            (upper_a, upper_b)
+        } else {
+            (global_a.wrapping_sub(upper_a), global_b.wrapping_sub(upper_b))
+        };
+        (
+            global_a.wrapping_sub(below_a).wrapping_sub(above_a),
+            global_b.wrapping_sub(below_b).wrapping_sub(above_b),
+        )
+    }
+
+    fn update_tick(&mut self, tick: i32, liquidity_delta: i128, is_upper: bool) -> Result<(), RangeError> {
+        let (current_tick, global_a, global_b) = (self.current_tick, self.fee_growth_global_a, self.fee_growth_global_b);
+        let entry = self.ticks.entry(tick).or_insert_with(|| {
+            // Growth below a newly initialized tick is assumed to have happened outside it
+            if tick <= current_tick {
+                Tick {
+                    fee_growth_outside_a: global_a,
+                    fee_growth_outside_b: global_b,
+                    ..Tick::default()
+                }
+            } else {
+                Tick::default()
+            }
+        });
+        entry.liquidity_gross = entry
+            .liquidity_gross
+            .checked_add_signed(liquidity_delta)
+            .ok_or(RangeError::InsufficientLiquidity)?;
+        let net_delta = if is_upper { -liquidity_delta } else { liquidity_delta };
+        entry.liquidity_net = entry.liquidity_net.checked_add(net_delta).ok_or(RangeError::Overflow)?;
+        if entry.liquidity_gross == 0 {
+            self.ticks.remove(&tick);
+        }
+        Ok(())
+    }
+
+    /// Adds (positive delta) or removes liquidity from a position, settling its
+    /// accrued fees first. Returns the token amounts deposited or released.
+    pub fn modify_position(&mut self, position: &mut Position, liquidity_delta: i128) -> Result<(u128, u128), RangeError> {
+        let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+        self.check_range(tick_lower, tick_upper)?;
+        let liquidity = position
+            .liquidity
+            .to_attos()
+            .checked_add_signed(liquidity_delta)
+            .ok_or(RangeError::InsufficientLiquidity)?;
+
+        // Ticks must exist while fees are settled: initialize before, clear after
+        if liquidity_delta > 0 {
+            self.update_tick(tick_lower, liquidity_delta, false)?;
+            self.update_tick(tick_upper, liquidity_delta, true)?;
+        }
+        self.accrue_fees(position);
+        if liquidity_delta < 0 {
+            self.update_tick(tick_lower, liquidity_delta, false)?;
+            self.update_tick(tick_upper, liquidity_delta, true)?;
+        }
+        position.liquidity = Amount::from_attos(liquidity);
+
+        if (tick_lower..tick_upper).contains(&self.current_tick) {
+            let active = self
+                .liquidity
+                .to_attos()
+                .checked_add_signed(liquidity_delta)
+                .ok_or(RangeError::InsufficientLiquidity)?;
+            self.liquidity = Amount::from_attos(active);
+        }
+        self.amounts_for_liquidity(tick_lower, tick_upper, liquidity_delta.unsigned_abs(), liquidity_delta > 0)
+    }
+
+    /// Moves fees earned since the last settlement into the position's owed amounts
+    pub fn accrue_fees(&self, position: &mut Position) {
+        let (inside_a, inside_b) = self.fee_growth_inside(position.tick_lower, position.tick_upper);
+        let liquidity = position.liquidity.to_attos();
+        let earned = |inside: u128, last: u128| {
+            mul_div(liquidity, inside.wrapping_sub(last), Q64).unwrap_or(0)
+        };
+        position.tokens_owed_a = position
+            .tokens_owed_a
+            .saturating_add(Amount::from_attos(earned(inside_a, position.fee_growth_inside_last_a)));
+        position.tokens_owed_b = position
+            .tokens_owed_b
+            .saturating_add(Amount::from_attos(earned(inside_b, position.fee_growth_inside_last_b)));
+        position.fee_growth_inside_last_a = inside_a;
+        position.fee_growth_inside_last_b = inside_b;
+    }
+
+    fn cross_tick(&mut self, tick: i32, a_to_b: bool) -> Result<(), RangeError> {
+        let Some(entry) = self.ticks.get_mut(&tick) else {
+            return Ok(());
+        };
+        entry.fee_growth_outside_a = self.fee_growth_global_a.wrapping_sub(entry.fee_growth_outside_a);
+        entry.fee_growth_outside_b = self.fee_growth_global_b.wrapping_sub(entry.fee_growth_outside_b);
+        // Moving down leaves the range that the tick's net liquidity opened
+        let net = if a_to_b { -entry.liquidity_net } else { entry.liquidity_net };
+        let active = self
+            .liquidity
+            .to_attos()
+            .checked_add_signed(net)
+            .ok_or(RangeError::InsufficientLiquidity)?;
+        self.liquidity = Amount::from_attos(active);
+        Ok(())
+    }
+
+    /// Simulates an exact-input swap and returns the updated pool
+    pub fn quote_swap(&self, a_to_b: bool, amount_in: u128) -> Result<(ConcentratedPool, SwapOutcome), RangeError> {
+        let mut pool = self.clone();
+        let outcome = pool.swap(a_to_b, amount_in)?;
+        Ok((pool, outcome))
+    }
+
+    /// Exact-input swap that walks initialized ticks, charging `fee_rate` on each step
+    pub fn swap(&mut self, a_to_b: bool, amount_in: u128) -> Result<SwapOutcome, RangeError> {
+        let bound = if a_to_b {
+            sqrt_price_at_tick(MIN_TICK)
+        } else {
+            sqrt_price_at_tick(MAX_TICK)
+        }
+        .ok_or(RangeError::InvalidPrice)?;
+        let mut outcome = SwapOutcome::default();
+        let mut remaining = amount_in;
+
+        for _ in 0..MAX_SWAP_STEPS {
+            if remaining == 0 || self.sqrt_price == bound {
+                break;
+            }
+            let next_tick = if a_to_b {
+                self.ticks.range(..=self.current_tick).next_back().map(|(tick, _)| *tick)
+            } else {
+                self.ticks.range(self.current_tick + 1..).next().map(|(tick, _)| *tick)
+            };
+            let target = match next_tick {
+                Some(tick) => sqrt_price_at_tick(tick).ok_or(RangeError::InvalidPrice)?,
+                None => bound,
+            };
+            let liquidity = self.liquidity.to_attos();
+            let price = self.sqrt_price;
+
+            let needed = if a_to_b {
+                delta_a(target, price, liquidity, true)
+            } else {
+                delta_b(price, target, liquidity, true)
+            }
+            .ok_or(RangeError::Overflow)?;
+            let available = remaining - swap_fee(remaining, self.fee_rate);
+
+            let (next_price, net_in, gross_in) = if liquidity == 0 {
+                (target, 0, 0)
+            } else if available >= needed {
+                let gross = gross_up(needed, self.fee_rate).ok_or(RangeError::Overflow)?.min(remaining);
+                (target, needed, gross)
+            } else {
+                let next_price = if a_to_b {
+                    // L * P / (L + dx * P)
+                    let scaled = mul_div(available, price, Q64).ok_or(RangeError::Overflow)?;
+                    mul_div(liquidity, price, liquidity.checked_add(scaled).ok_or(RangeError::Overflow)?)
+                        .ok_or(RangeError::Overflow)?
+                        .saturating_add(1)
+                        .max(target)
+                } else {
+                    // P + dy / L
+                    price
+                        .checked_add(mul_div(available, Q64, liquidity).ok_or(RangeError::Overflow)?)
+                        .ok_or(RangeError::Overflow)?
+                        .min(target)
+                };
+                (next_price, available, remaining)
+            };
+
+            let out = if a_to_b {
+                delta_b(next_price, price, liquidity, false)
+            } else {
+                delta_a(price, next_price, liquidity, false)
+            }
+            .ok_or(RangeError::Overflow)?;
+            let fee = gross_in - net_in;
+            if liquidity > 0 && fee > 0 {
+                let growth = mul_div(fee, Q64, liquidity).ok_or(RangeError::Overflow)?;
+                if a_to_b {
+                    self.fee_growth_global_a = self.fee_growth_global_a.wrapping_add(growth);
+                } else {
+                    self.fee_growth_global_b = self.fee_growth_global_b.wrapping_add(growth);
+                }
+            }
+            remaining -= gross_in;
+            outcome.amount_in += gross_in;
+            outcome.amount_out += out;
+            outcome.fee += fee;
+            self.sqrt_price = next_price;
+
+            match next_tick {
+                Some(tick) if next_price == target => {
+                    self.cross_tick(tick, a_to_b)?;
+                    self.current_tick = if a_to_b { tick - 1 } else { tick };
+                }
+                _ => self.current_tick = tick_at_sqrt_price(next_price),
+            }
+        }
+
+        if outcome.amount_out == 0 {
+            return Err(RangeError::InsufficientLiquidity);
+        }
+        Ok(outcome)
+    }
+}
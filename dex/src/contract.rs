@@ -1,33 +1,23 @@
 #![cfg_attr(target_arch = "wasm32", no_main)]
 
-use dex::{BridgeToken, DexAbi, DexInstantiationArgument, DexOperation, DexResponse, DexState, Pool};
+use dex::{
+    admin::{AdminAction, AdminActionKind},
+    concentrated::{self, ConcentratedPool, Position, RangeError},
+    curve::Curve,
+    history::{self, SwapDirection, Trade},
+    math, oracle,
+    orders::{OrderSide, OrderStatus},
+    rewards::RewardProgram,
+    stats::VolumeBucket,
+    weighted::WeightedPool,
+    acting_account, BridgeToken, DexAbi, DexError, DexEvent, DexInstantiationArgument, DexOperation,
+    DexResponse, DexState, FlashBorrowerAbi, FlashSwapCallback, Pool,
+    DEX_STREAM_NAME,
+};
 use linera_sdk::{
-    linera_base_types::{AccountOwner, Amount},
+    linera_base_types::{AccountOwner, Amount, ApplicationId},
     Contract, ContractRuntime,
 };
-use thiserror::Error;
-
-#[derive(Error, Debug)]
-pub enum DexError {
-    #[error("Insufficient balance")]
-    InsufficientBalance,
-    #[error("Pool does not exist")]
-    PoolNotFound,
-    #[error("Pool already exists")]
-    PoolAlreadyExists,
-    #[error("Invalid pool state")]
-    InvalidPoolState,
-    #[error("Insufficient pool reserves")]
-    InsufficientPoolReserves,
-    #[error("Invalid calculation")]
-    InvalidCalculation,
-    #[error("Pool has no shares")]
-    NoPoolShares,
-    #[error("Zero reserve")]
-    ZeroReserve,
-    #[error("Insufficient output reserve")]
-    InsufficientOutputReserve,
-}
 
 pub struct DexContract {
     state: DexState,
@@ -44,7 +34,7 @@ impl Contract for DexContract {
     type Message = ();
     type InstantiationArgument = DexInstantiationArgument;
     type Parameters = ();
-    type EventValue = ();
+    type EventValue = DexEvent;
     
     async fn load(runtime: ContractRuntime<Self>) -> Self {
         DexContract {
@@ -55,15 +45,22 @@ impl Contract for DexContract {
 
     async fn instantiate(&mut self, argument: Self::InstantiationArgument) {
         self.state.bridge_tracker_app = argument.bridge_tracker_app;
+        self.state.owner = argument
+            .owner
+            .or_else(|| self.runtime.authenticated_signer());
+        self.state.admins = argument.admins.into_iter().collect();
     }
 
     async fn execute_operation(&mut self, operation: DexOperation) -> DexResponse {
+        if self.state.paused && operation.is_halted_by_pause() {
+            return DexResponse::Error(DexError::Paused.to_string());
+        }
         match operation {
             DexOperation::SwapTokens { from_token, to_token, amount } => {
                 self.swap_tokens(from_token, to_token, amount).await
             },
-            DexOperation::CreatePool { token_a, token_b, amount_a, amount_b, fee_rate } => {
-                self.create_pool(token_a, token_b, amount_a, amount_b, fee_rate).await
+            DexOperation::CreatePool { token_a, token_b, amount_a, amount_b, fee_rate, curve } => {
+                self.create_pool(token_a, token_b, amount_a, amount_b, fee_rate, curve.unwrap_or_default()).await
             },
             DexOperation::AddLiquidity { token_a, token_b, amount_a, amount_b } => {
                 self.add_liquidity(token_a, token_b, amount_a, amount_b).await
@@ -71,12 +68,118 @@ impl Contract for DexContract {
             DexOperation::RemoveLiquidity { token_a, token_b, share_amount } => {
                 self.remove_liquidity(token_a, token_b, share_amount).await
             },
+            DexOperation::AddLiquiditySingle { token_a, token_b, token, amount, min_shares } => {
+                self.add_liquidity_single(token_a, token_b, token, amount, min_shares).await
+            },
+            DexOperation::RemoveLiquiditySingle { token_a, token_b, share_amount, token, min_amount } => {
+                self.remove_liquidity_single(token_a, token_b, share_amount, token, min_amount).await
+            },
             DexOperation::MintBridgeToken { token, user, amount } => {
                 self.mint_bridge_token(token, user, amount).await
             },
             DexOperation::BurnBridgeToken { token, user, amount } => {
                 self.burn_bridge_token(token, user, amount).await
             },
+            DexOperation::CreateToken { symbol, name, decimals, initial_supply, mintable } => {
+                self.create_token(symbol, name, decimals, initial_supply, mintable).await
+            },
+            DexOperation::MintToken { symbol, recipient, amount } => {
+                self.mint_token(symbol, recipient, amount).await
+            },
+            DexOperation::LaunchToken {
+                symbol,
+                name,
+                decimals,
+                initial_supply,
+                mintable,
+                quote_token,
+                token_amount,
+                quote_amount,
+                fee_rate,
+            } => {
+                self.launch_token(
+                    symbol, name, decimals, initial_supply, mintable,
+                    quote_token, token_amount, quote_amount, fee_rate,
+                ).await
+            },
+            DexOperation::GetTwap { token_a, token_b, window_secs } => {
+                self.get_twap(token_a, token_b, window_secs)
+            },
+            DexOperation::PlaceLimitOrder { token_a, token_b, side, amount_in, min_price, expiry } => {
+                self.place_limit_order(token_a, token_b, side, amount_in, min_price, expiry).await
+            },
+            DexOperation::CancelLimitOrder { order_id } => {
+                self.cancel_limit_order(order_id).await
+            },
+            DexOperation::FlashSwap { token_a, token_b, amount_a_out, amount_b_out, receiver, data } => {
+                self.flash_swap(token_a, token_b, amount_a_out, amount_b_out, receiver, data).await
+            },
+            DexOperation::CreateConcentratedPool { token_a, token_b, fee_rate, tick_spacing, initial_price } => {
+                self.create_concentrated_pool(token_a, token_b, fee_rate, tick_spacing, initial_price).await
+            },
+            DexOperation::MintPosition { token_a, token_b, tick_lower, tick_upper, amount_a, amount_b } => {
+                self.mint_position(token_a, token_b, tick_lower, tick_upper, amount_a, amount_b).await
+            },
+            DexOperation::BurnPosition { position_id, liquidity } => {
+                self.burn_position(position_id, liquidity).await
+            },
+            DexOperation::CollectFees { position_id } => {
+                self.collect_fees(position_id).await
+            },
+            DexOperation::TransferPosition { position_id, recipient } => {
+                self.transfer_position(position_id, recipient).await
+            },
+            DexOperation::SwapConcentrated { from_token, to_token, amount, min_received } => {
+                self.swap_concentrated(from_token, to_token, amount, min_received).await
+            },
+            DexOperation::CreateWeightedPool { tokens, weights, amounts, fee_rate } => {
+                self.create_weighted_pool(tokens, weights, amounts, fee_rate).await
+            },
+            DexOperation::JoinWeightedPool { pool_id, token, amount, min_shares } => {
+                self.join_weighted_pool(pool_id, token, amount, min_shares).await
+            },
+            DexOperation::ExitWeightedPool { pool_id, shares, token, min_amount } => {
+                self.exit_weighted_pool(pool_id, shares, token, min_amount).await
+            },
+            DexOperation::SwapWeighted { pool_id, from_token, to_token, amount, min_received } => {
+                self.swap_weighted(pool_id, from_token, to_token, amount, min_received).await
+            },
+            DexOperation::CreateRewardProgram { token_a, token_b, reward_token, amount, duration_secs } => {
+                self.create_reward_program(token_a, token_b, reward_token, amount, duration_secs).await
+            },
+            DexOperation::FundRewardProgram { program_id, amount, duration_secs } => {
+                self.fund_reward_program(program_id, amount, duration_secs).await
+            },
+            DexOperation::ClaimRewards { program_id } => {
+                self.claim_rewards(program_id).await
+            },
+            DexOperation::AddAdmin { admin } => {
+                self.add_admin(admin).await
+            },
+            DexOperation::RemoveAdmin { admin } => {
+                self.remove_admin(admin).await
+            },
+            DexOperation::RenounceAdmin => {
+                self.renounce_admin().await
+            },
+            DexOperation::TransferOwnership { new_owner } => {
+                self.transfer_ownership(new_owner).await
+            },
+            DexOperation::RenounceOwnership => {
+                self.renounce_ownership().await
+            },
+            DexOperation::SetPoolFee { token_a, token_b, fee_rate } => {
+                self.set_pool_fee(token_a, token_b, fee_rate).await
+            },
+            DexOperation::SetPaused { paused } => {
+                self.set_paused(paused).await
+            },
+            DexOperation::SetBridgeTrackerApp { app } => {
+                self.set_bridge_tracker_app(app).await
+            },
+            DexOperation::SetTrustedMinter { network, minter } => {
+                self.set_trusted_minter(network, minter).await
+            },
         }
     }
 
@@ -86,35 +189,112 @@ impl Contract for DexContract {
 }
 
 impl DexContract {
-    fn get_user(&mut self) -> String {
-        self.runtime.authenticated_signer().unwrap().to_string()
+    fn get_user(&mut self) -> Result<AccountOwner, DexError> {
+        let caller = self.runtime.authenticated_caller_id();
+        acting_account(caller, self.runtime.authenticated_signer())
+    }
+
+    fn get_user_balance(&self, user: &AccountOwner, token: &BridgeToken) -> Amount {
+        self.state.balance(user, token)
     }
 
-    fn get_user_balance(&self, user: &str, token: &BridgeToken) -> Amount {
-        self.state.user_balances
-            .get(&(user.to_string(), token.clone()))
+    fn set_user_balance(&mut self, user: &AccountOwner, token: &BridgeToken, amount: Amount) {
+        self.state.set_balance(user, token, amount);
+    }
+
+    fn get_shares(&self, user: &AccountOwner, pool_key: &(BridgeToken, BridgeToken)) -> Amount {
+        self.state.lp_shares
+            .get(&(*user, pool_key.0.clone(), pool_key.1.clone()))
             .copied()
             .unwrap_or_default()
     }
 
-    fn set_user_balance(&mut self, user: &str, token: &BridgeToken, amount: Amount) {
-        self.state.user_balances.insert((user.to_string(), token.clone()), amount);
+    fn set_shares(&mut self, user: &AccountOwner, pool_key: &(BridgeToken, BridgeToken), shares: Amount) {
+        self.settle_rewards(user, pool_key);
+        self.state.lp_shares.insert((*user, pool_key.0.clone(), pool_key.1.clone()), shares);
     }
 
-    async fn mint_bridge_token(&mut self, token: BridgeToken, user: String, amount: Amount) -> DexResponse {
+    fn emit(&mut self, event: DexEvent) {
+        self.runtime.emit(DEX_STREAM_NAME.into(), &event);
+    }
+
+    /// Emits every reward program on the pool up to now; call before its total shares change
+    fn update_rewards(&mut self, pool_key: &(BridgeToken, BridgeToken)) {
+        let now = self.runtime.system_time().micros();
+        let Some(total_shares) = self.state.pools.get(pool_key).map(|pool| pool.total_shares) else {
+            return;
+        };
+        for program in self.state.reward_programs.values_mut() {
+            if (&program.token_a, &program.token_b) == (&pool_key.0, &pool_key.1) {
+                program.accrue(now, total_shares);
+            }
+        }
+    }
+
+    /// Books what a holder earned on their current shares; call before their shares change
+    fn settle_rewards(&mut self, user: &AccountOwner, pool_key: &(BridgeToken, BridgeToken)) {
+        self.update_rewards(pool_key);
+        let shares = self.get_shares(user, pool_key);
+        for program in self.state.reward_programs.values() {
+            if (&program.token_a, &program.token_b) != (&pool_key.0, &pool_key.1) {
+                continue;
+            }
+            let stake = self.state.reward_stakes.entry((program.id, *user)).or_default();
+            stake.pending = program.earned(shares, stake);
+            stake.reward_per_share_paid = program.reward_per_share;
+        }
+    }
+
+    /// Extends the pool's price accumulators; call before its reserves change
+    fn record_price(&mut self, pool_key: &(BridgeToken, BridgeToken)) {
+        let now = self.runtime.system_time().micros();
+        let Some(pool) = self.state.pools.get(pool_key) else {
+            return;
+        };
+        let observations = self.state.observations.entry(pool_key.clone()).or_default();
+        oracle::record(observations, now, &pool.curve, pool.reserve_a.to_attos(), pool.reserve_b.to_attos());
+    }
+
+    fn get_twap(&mut self, token_a: BridgeToken, token_b: BridgeToken, window_secs: u64) -> DexResponse {
+        let Some(pool_key) = self.state.find_pool_key(&token_a, &token_b) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let pool = &self.state.pools[&pool_key];
+        let observations = self.state.observations.get(&pool_key).map(Vec::as_slice).unwrap_or_default();
+        let now = self.runtime.system_time().micros();
+        match oracle::twap(pool, observations, now, window_secs.saturating_mul(1_000_000)) {
+            Some(twap) => DexResponse::Twap(twap),
+            None => DexResponse::Error("Not enough price history".to_string()),
+        }
+    }
+
+    fn require_minter(&mut self, token: &BridgeToken) -> Result<(), DexError> {
+        let caller = self.runtime.authenticated_caller_id();
+        self.state.require_minter(caller, token)
+    }
+
+    async fn mint_bridge_token(&mut self, token: BridgeToken, user: AccountOwner, amount: Amount) -> DexResponse {
+        if let Err(error) = self.require_minter(&token) {
+            return DexResponse::Error(error.to_string());
+        }
         let current_balance = self.get_user_balance(&user, &token);
         let new_balance = Amount::from_attos(current_balance.to_attos() + amount.to_attos());
         self.set_user_balance(&user, &token, new_balance);
+        self.emit(DexEvent::Deposit { user, token, amount });
         DexResponse::Ok
     }
 
-    async fn burn_bridge_token(&mut self, token: BridgeToken, user: String, amount: Amount) -> DexResponse {
+    async fn burn_bridge_token(&mut self, token: BridgeToken, user: AccountOwner, amount: Amount) -> DexResponse {
+        if let Err(error) = self.require_minter(&token) {
+            return DexResponse::Error(error.to_string());
+        }
         let current_balance = self.get_user_balance(&user, &token);
         if current_balance.to_attos() < amount.to_attos() {
             return DexResponse::Error("Insufficient balance".to_string());
         }
         let new_balance = Amount::from_attos(current_balance.to_attos() - amount.to_attos());
         self.set_user_balance(&user, &token, new_balance);
+        self.emit(DexEvent::Withdrawal { user, token, amount });
         DexResponse::Ok
     }
 
@@ -125,26 +305,45 @@ impl DexContract {
         amount_a: Amount, 
         amount_b: Amount,
         fee_rate: u32,
+        curve: Curve,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        self.open_pool(&user, token_a, token_b, amount_a, amount_b, fee_rate, curve)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn open_pool(
+        &mut self,
+        user: &AccountOwner,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        amount_a: Amount,
+        amount_b: Amount,
+        fee_rate: u32,
+        curve: Curve,
     ) -> DexResponse {
         let pool_key = (token_a.clone(), token_b.clone());
-        
-        if self.state.pools.contains_key(&pool_key) {
-            return DexResponse::Error("Pool already exists".to_string());
-        }
+        let shares = match self.state.check_new_pool(&token_a, &token_b, amount_a, amount_b, fee_rate, &curve) {
+            Ok(shares) => shares,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let creator_shares = Amount::from_attos(shares - math::MINIMUM_LIQUIDITY);
+        let shares = Amount::from_attos(shares);
 
-        let user = self.get_user();
-        
         // Check user has enough tokens
-        if self.get_user_balance(&user, &token_a).to_attos() < amount_a.to_attos() ||
-           self.get_user_balance(&user, &token_b).to_attos() < amount_b.to_attos() {
+        if self.get_user_balance(user, &token_a).to_attos() < amount_a.to_attos() ||
+           self.get_user_balance(user, &token_b).to_attos() < amount_b.to_attos() {
             return DexResponse::Error("Insufficient balance".to_string());
         }
 
         // Deduct tokens from user
-        let balance_a = self.get_user_balance(&user, &token_a);
-        let balance_b = self.get_user_balance(&user, &token_b);
-        self.set_user_balance(&user, &token_a, Amount::from_attos(balance_a.to_attos() - amount_a.to_attos()));
-        self.set_user_balance(&user, &token_b, Amount::from_attos(balance_b.to_attos() - amount_b.to_attos()));
+        let balance_a = self.get_user_balance(user, &token_a);
+        self.set_user_balance(user, &token_a, Amount::from_attos(balance_a.to_attos() - amount_a.to_attos()));
+        let balance_b = self.get_user_balance(user, &token_b);
+        self.set_user_balance(user, &token_b, Amount::from_attos(balance_b.to_attos() - amount_b.to_attos()));
 
         // Create pool
         let pool = Pool {
@@ -152,66 +351,388 @@ impl DexContract {
             token_b: token_b.clone(),
             reserve_a: amount_a,
             reserve_b: amount_b,
-            total_shares: amount_a, // Initial shares = amount_a
+            total_shares: shares,
             fee_rate,
+            curve,
         };
 
-        self.state.pools.insert(pool_key, pool);
+        self.state.pools.insert(pool_key.clone(), pool);
+        self.set_shares(user, &pool_key, creator_shares);
+        self.record_price(&pool_key);
+        self.emit(DexEvent::PoolCreated {
+            creator: *user,
+            token_a: token_a.clone(),
+            token_b: token_b.clone(),
+            fee_rate,
+        });
+        self.emit(DexEvent::Mint {
+            provider: *user,
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            shares: creator_shares,
+        });
         DexResponse::PoolCreated { success: true }
     }
 
+    async fn create_token(
+        &mut self,
+        symbol: String,
+        name: String,
+        decimals: u8,
+        initial_supply: Amount,
+        mintable: bool,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        match self.state.register_token(&user, symbol, name, decimals, initial_supply, mintable) {
+            Ok(token) => DexResponse::TokenCreated { token },
+            Err(error) => DexResponse::Error(error.to_string()),
+        }
+    }
+
+    async fn mint_token(&mut self, symbol: String, recipient: AccountOwner, amount: Amount) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        match self.state.mint_token(&user, &symbol, &recipient, amount) {
+            Ok(()) => DexResponse::Ok,
+            Err(error) => DexResponse::Error(error.to_string()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn launch_token(
+        &mut self,
+        symbol: String,
+        name: String,
+        decimals: u8,
+        initial_supply: Amount,
+        mintable: bool,
+        quote_token: BridgeToken,
+        token_amount: Amount,
+        quote_amount: Amount,
+        fee_rate: u32,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        // Check the pool up front: a failed launch must not leave the token registered
+        let launch = self.state.check_launch(&user, &symbol, initial_supply, &quote_token, token_amount, quote_amount, fee_rate);
+        if let Err(error) = launch {
+            return DexResponse::Error(error.to_string());
+        }
+        let token = match self.state.register_token(&user, symbol, name, decimals, initial_supply, mintable) {
+            Ok(token) => token,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        self.open_pool(&user, token, quote_token, token_amount, quote_amount, fee_rate, Curve::ConstantProduct)
+    }
+
     async fn swap_tokens(
         &mut self,
         from_token: BridgeToken,
         to_token: BridgeToken,
         amount: Amount,
     ) -> DexResponse {
-        let user = self.get_user();
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
         
         // Check user has enough tokens
         if self.get_user_balance(&user, &from_token).to_attos() < amount.to_attos() {
             return DexResponse::Error("Insufficient balance".to_string());
         }
 
-        let pool_key = (from_token.clone(), to_token.clone());
-        let pool = match self.state.pools.get_mut(&pool_key) {
-            Some(pool) => pool,
-            None => return DexResponse::Error("Pool not found".to_string()),
+        let Some(pool_key) = self.state.find_pool_key(&from_token, &to_token) else {
+            return DexResponse::Error("Pool not found".to_string());
+        };
+        let amount_out = match self.execute_swap(&user, &pool_key, &from_token, &to_token, amount) {
+            Ok(amount_out) => amount_out,
+            Err(error) => return DexResponse::Error(error.to_string()),
         };
 
-        // Calculate output using CPMM formula: dy = (y * dx) / (x + dx)
-        let (input_reserve, output_reserve) = if from_token == pool.token_a {
-            (pool.reserve_a, pool.reserve_b)
+        // Update user balances
+        let from_balance = self.get_user_balance(&user, &from_token);
+        self.set_user_balance(&user, &from_token, Amount::from_attos(from_balance.to_attos() - amount.to_attos()));
+        let to_balance = self.get_user_balance(&user, &to_token);
+        self.set_user_balance(&user, &to_token, Amount::from_attos(to_balance.to_attos() + amount_out.to_attos()));
+
+        self.match_limit_orders(&pool_key);
+        DexResponse::SwapResult { received: amount_out }
+    }
+
+    /// Trades against a pool and records the swap. The caller settles `trader`'s balances.
+    fn execute_swap(
+        &mut self,
+        trader: &AccountOwner,
+        pool_key: &(BridgeToken, BridgeToken),
+        from_token: &BridgeToken,
+        to_token: &BridgeToken,
+        amount: Amount,
+    ) -> Result<Amount, DexError> {
+        let pool = self.state.pools.get(pool_key).ok_or(DexError::PoolNotFound)?;
+        let (output_amount_u128, fee_u128) = pool.quote_swap(from_token, amount)?;
+        let amount_u128 = amount.to_attos();
+        self.record_price(pool_key);
+        let pool = self.state.pools.get_mut(pool_key).ok_or(DexError::PoolNotFound)?;
+        pool.apply_swap(from_token, amount_u128, output_amount_u128);
+        let (token_a, token_b) = (pool.token_a.clone(), pool.token_b.clone());
+        let direction = if *from_token == token_a {
+            SwapDirection::AToB
         } else {
-            (pool.reserve_b, pool.reserve_a)
+            SwapDirection::BToA
         };
+        let amount_out = Amount::from_attos(output_amount_u128);
 
-        let input_u128 = input_reserve.to_attos();
-        let output_u128 = output_reserve.to_attos();
-        let amount_u128 = amount.to_attos();
-        
-        let output_amount_u128 = (output_u128 * amount_u128) / (input_u128 + amount_u128);
-        
-        if output_amount_u128 >= output_u128 {
-            return DexResponse::Error("Insufficient pool reserves".to_string());
+        let now = self.runtime.system_time().micros();
+        let (volume_a, volume_b) = match direction {
+            SwapDirection::AToB => (amount, amount_out),
+            SwapDirection::BToA => (amount_out, amount),
+        };
+        let fee = Amount::from_attos(fee_u128);
+        let (fees_a, fees_b) = match direction {
+            SwapDirection::AToB => (fee, Amount::ZERO),
+            SwapDirection::BToA => (Amount::ZERO, fee),
+        };
+        self.state.volumes.entry(pool_key.clone()).or_default().record(now, VolumeBucket {
+            start: now,
+            volume_a,
+            volume_b,
+            fees_a,
+            fees_b,
+        });
+
+        let trade = Trade {
+            timestamp: now,
+            token_a: token_a.clone(),
+            token_b: token_b.clone(),
+            direction,
+            amount_in: amount,
+            amount_out,
+            price: history::execution_price(direction, amount, amount_out),
+            trader: *trader,
+        };
+        self.state.trades.entry(pool_key.clone()).or_default().push(trade);
+
+        self.emit(DexEvent::Swap {
+            trader: *trader,
+            token_in: from_token.clone(),
+            token_out: to_token.clone(),
+            amount_in: amount,
+            amount_out,
+        });
+        if fee_u128 > 0 {
+            self.emit(DexEvent::FeeCollected {
+                token_a,
+                token_b,
+                token: from_token.clone(),
+                amount: fee,
+            });
         }
 
-        // Update pool reserves
-        if from_token == pool.token_a {
-            pool.reserve_a = Amount::from_attos(pool.reserve_a.to_attos() + amount_u128);
-            pool.reserve_b = Amount::from_attos(pool.reserve_b.to_attos() - output_amount_u128);
-        } else {
-            pool.reserve_b = Amount::from_attos(pool.reserve_b.to_attos() + amount_u128);
-            pool.reserve_a = Amount::from_attos(pool.reserve_a.to_attos() - output_amount_u128);
+        Ok(amount_out)
+    }
+
+    /// Lends pool output to `receiver` and calls it back. Panics, reverting the
+    /// whole operation, unless the repayment restores the invariant plus fees.
+    async fn flash_swap(
+        &mut self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        amount_a_out: Amount,
+        amount_b_out: Amount,
+        receiver: ApplicationId,
+        data: Vec<u8>,
+    ) -> DexResponse {
+        let initiator = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let pool_key = (token_a.clone(), token_b.clone());
+        let Some(pool) = self.state.pools.get(&pool_key) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        if (amount_a_out.is_zero() && amount_b_out.is_zero())
+            || amount_a_out >= pool.reserve_a
+            || amount_b_out >= pool.reserve_b
+        {
+            return DexResponse::Error(DexError::InsufficientPoolReserves.to_string());
         }
+        let old_reserves = (pool.reserve_a.to_attos(), pool.reserve_b.to_attos());
+        let (fee_rate, curve) = (pool.fee_rate, pool.curve);
+        let borrower = AccountOwner::from(receiver);
 
-        // Update user balances
-        let from_balance = self.get_user_balance(&user, &from_token);
-        let to_balance = self.get_user_balance(&user, &to_token);
-        self.set_user_balance(&user, &from_token, Amount::from_attos(from_balance.to_attos() - amount_u128));
-        self.set_user_balance(&user, &to_token, Amount::from_attos(to_balance.to_attos() + output_amount_u128));
+        // Send the output to the receiver before calling it
+        self.record_price(&pool_key);
+        let pool = self.state.pools.get_mut(&pool_key).expect("pool exists");
+        pool.reserve_a = Amount::from_attos(pool.reserve_a.to_attos() - amount_a_out.to_attos());
+        pool.reserve_b = Amount::from_attos(pool.reserve_b.to_attos() - amount_b_out.to_attos());
+        for (token, amount) in [(&token_a, amount_a_out), (&token_b, amount_b_out)] {
+            let balance = self.get_user_balance(&borrower, token);
+            self.set_user_balance(&borrower, token, Amount::from_attos(balance.to_attos() + amount.to_attos()));
+        }
+
+        let callback = FlashSwapCallback {
+            initiator,
+            token_a: token_a.clone(),
+            token_b: token_b.clone(),
+            amount_a_out,
+            amount_b_out,
+            data,
+        };
+        let repayment = self
+            .runtime
+            .call_application(true, receiver.with_abi::<FlashBorrowerAbi>(), &callback);
+
+        // Run the receiver's trades in other pools, then pull the repayment back into this one
+        let mut leg_pools = Vec::new();
+        for leg in &repayment.swaps {
+            let leg_pool = self
+                .state
+                .find_pool_key(&leg.from_token, &leg.to_token)
+                .filter(|leg_pool| *leg_pool != pool_key)
+                .expect("Flash swap leg must trade in another pool");
+            let from_balance = self.get_user_balance(&borrower, &leg.from_token);
+            assert!(from_balance >= leg.amount, "Flash swap leg exceeds receiver balance");
+            let amount_out = self
+                .execute_swap(&borrower, &leg_pool, &leg.from_token, &leg.to_token, leg.amount)
+                .unwrap_or_else(|error| panic!("Flash swap leg failed: {error}"));
+            self.set_user_balance(&borrower, &leg.from_token, Amount::from_attos(from_balance.to_attos() - leg.amount.to_attos()));
+            let to_balance = self.get_user_balance(&borrower, &leg.to_token);
+            self.set_user_balance(&borrower, &leg.to_token, Amount::from_attos(to_balance.to_attos() + amount_out.to_attos()));
+            leg_pools.push(leg_pool);
+        }
+        for (token, amount) in [(&token_a, repayment.amount_a), (&token_b, repayment.amount_b)] {
+            let balance = self.get_user_balance(&borrower, token);
+            assert!(balance >= amount, "Flash swap repayment exceeds receiver balance");
+            self.set_user_balance(&borrower, token, Amount::from_attos(balance.to_attos() - amount.to_attos()));
+        }
+        let pool = self.state.pools.get_mut(&pool_key).expect("pool exists");
+        pool.reserve_a = Amount::from_attos(pool.reserve_a.to_attos() + repayment.amount_a.to_attos());
+        pool.reserve_b = Amount::from_attos(pool.reserve_b.to_attos() + repayment.amount_b.to_attos());
+        let new_reserves = (pool.reserve_a.to_attos(), pool.reserve_b.to_attos());
+
+        assert!(
+            curve.flash_repaid(
+                old_reserves,
+                (amount_a_out.to_attos(), amount_b_out.to_attos()),
+                new_reserves,
+                fee_rate,
+            ),
+            "Flash swap did not restore the pool invariant"
+        );
+
+        self.emit(DexEvent::FlashSwap {
+            receiver: borrower,
+            token_a,
+            token_b,
+            amount_a_out,
+            amount_b_out,
+            amount_a_in: repayment.amount_a,
+            amount_b_in: repayment.amount_b,
+        });
+        self.match_limit_orders(&pool_key);
+        for leg_pool in leg_pools {
+            self.match_limit_orders(&leg_pool);
+        }
+        DexResponse::Ok
+    }
+
+    async fn place_limit_order(
+        &mut self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        side: OrderSide,
+        amount_in: Amount,
+        min_price: Amount,
+        expiry: Option<u64>,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let now = self.runtime.system_time().micros();
+        let order_id = match self.state.open_order(&user, &token_a, &token_b, side, amount_in, min_price, expiry, now) {
+            Ok(order_id) => order_id,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let order = &self.state.orders[&order_id];
+        let pool_key = (order.token_a.clone(), order.token_b.clone());
+        self.match_limit_orders(&pool_key);
+        DexResponse::OrderPlaced { order_id }
+    }
+
+    async fn cancel_limit_order(&mut self, order_id: u64) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let now = self.runtime.system_time().micros();
+        match self.state.cancel_order(&user, order_id, now) {
+            Ok(()) => DexResponse::Ok,
+            Err(error) => DexResponse::Error(error.to_string()),
+        }
+    }
+
+    /// Fills every open order on the pool whose limit the current price satisfies,
+    /// repeating until a pass fills nothing since fills move the price
+    fn match_limit_orders(&mut self, pool_key: &(BridgeToken, BridgeToken)) {
+        loop {
+            let now = self.runtime.system_time().micros();
+            let mut open_orders = self
+                .state
+                .orders
+                .values()
+                .filter(|order| {
+                    order.status == OrderStatus::Open
+                        && (&order.token_a, &order.token_b) == (&pool_key.0, &pool_key.1)
+                })
+                .map(|order| order.id)
+                .collect::<Vec<_>>();
+            open_orders.sort_unstable();
+
+            let mut filled_any = false;
+            for order_id in open_orders {
+                let order = self.state.orders[&order_id].clone();
+                if order.is_expired(now) {
+                    self.state.close_order(order_id, OrderStatus::Expired, now);
+                    continue;
+                }
+
+                let Some(pool) = self.state.pools.get(pool_key) else {
+                    return;
+                };
+                if !order.is_fillable(pool) {
+                    continue;
+                }
+                let (input_token, output_token) = order.tokens();
+                let Ok(amount_out) =
+                    self.execute_swap(&order.owner, pool_key, &input_token, &output_token, order.amount_in)
+                else {
+                    continue;
+                };
+
+                let balance = self.get_user_balance(&order.owner, &output_token);
+                self.set_user_balance(&order.owner, &output_token, Amount::from_attos(balance.to_attos() + amount_out.to_attos()));
+                if let Some(order) = self.state.orders.get_mut(&order_id) {
+                    order.status = OrderStatus::Filled;
+                    order.amount_out = amount_out;
+                    order.closed_at = Some(now);
+                }
+                filled_any = true;
+            }
 
-        DexResponse::SwapResult { received: Amount::from_attos(output_amount_u128) }
+            if !filled_any {
+                break;
+            }
+        }
     }
 
     async fn add_liquidity(
@@ -221,7 +742,164 @@ impl DexContract {
         amount_a: Amount,
         amount_b: Amount,
     ) -> DexResponse {
-        DexResponse::Error("Not implemented".to_string())
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(pool_key) = self.state.find_pool_key(&token_a, &token_b) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        // Orient the amounts to the pool's token order
+        let (amount_a, amount_b) = if pool_key.0 == token_a {
+            (amount_a, amount_b)
+        } else {
+            (amount_b, amount_a)
+        };
+        if self.get_user_balance(&user, &pool_key.0) < amount_a || self.get_user_balance(&user, &pool_key.1) < amount_b {
+            return DexResponse::Error(DexError::InsufficientBalance.to_string());
+        }
+
+        let quote = self.state.pools[&pool_key].quote_deposit(amount_a.to_attos(), amount_b.to_attos());
+        let (shares, used_a, used_b) = match quote {
+            Ok(quote) => quote,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        self.deposit_liquidity(&user, &pool_key, shares, used_a, used_b);
+        DexResponse::LiquidityAdded { shares_minted: Amount::from_attos(shares) }
+    }
+
+    /// Moves a quoted deposit from `user`'s balances into the pool and mints the shares
+    fn deposit_liquidity(
+        &mut self,
+        user: &AccountOwner,
+        pool_key: &(BridgeToken, BridgeToken),
+        shares: u128,
+        amount_a: u128,
+        amount_b: u128,
+    ) {
+        let (token_a, token_b) = pool_key.clone();
+        let balance_a = self.get_user_balance(user, &token_a);
+        self.set_user_balance(user, &token_a, Amount::from_attos(balance_a.to_attos() - amount_a));
+        let balance_b = self.get_user_balance(user, &token_b);
+        self.set_user_balance(user, &token_b, Amount::from_attos(balance_b.to_attos() - amount_b));
+
+        self.record_price(pool_key);
+        self.update_rewards(pool_key);
+        let pool = self.state.pools.get_mut(pool_key).expect("pool exists");
+        pool.apply_deposit(shares, amount_a, amount_b);
+        let user_shares = self.get_shares(user, pool_key);
+        self.set_shares(user, pool_key, Amount::from_attos(user_shares.to_attos() + shares));
+
+        self.emit(DexEvent::Mint {
+            provider: *user,
+            token_a,
+            token_b,
+            amount_a: Amount::from_attos(amount_a),
+            amount_b: Amount::from_attos(amount_b),
+            shares: Amount::from_attos(shares),
+        });
+    }
+
+    async fn add_liquidity_single(
+        &mut self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        token: BridgeToken,
+        amount: Amount,
+        min_shares: Amount,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(pool_key) = self.state.find_pool_key(&token_a, &token_b) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let other = match &pool_key {
+            (a, b) if *a == token => b.clone(),
+            (a, b) if *b == token => a.clone(),
+            _ => return DexResponse::Error(DexError::TokenNotInPool.to_string()),
+        };
+        if amount.is_zero() || self.get_user_balance(&user, &token) < amount {
+            return DexResponse::Error(DexError::InsufficientBalance.to_string());
+        }
+
+        // Quote the swap and the deposit first so nothing moves unless both succeed
+        let quote = self.state.pools[&pool_key].quote_single_deposit(&token, amount.to_attos());
+        let (swap_in, swap_out, shares, used_a, used_b) = match quote {
+            Ok(quote) => quote,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        if shares < min_shares.to_attos() {
+            return DexResponse::Error(DexError::SlippageExceeded.to_string());
+        }
+
+        if swap_in > 0 {
+            if let Err(error) = self.execute_swap(&user, &pool_key, &token, &other, Amount::from_attos(swap_in)) {
+                return DexResponse::Error(error.to_string());
+            }
+            let token_balance = self.get_user_balance(&user, &token);
+            self.set_user_balance(&user, &token, Amount::from_attos(token_balance.to_attos() - swap_in));
+            let other_balance = self.get_user_balance(&user, &other);
+            self.set_user_balance(&user, &other, Amount::from_attos(other_balance.to_attos() + swap_out));
+        }
+        self.deposit_liquidity(&user, &pool_key, shares, used_a, used_b);
+        self.match_limit_orders(&pool_key);
+        DexResponse::LiquidityAdded { shares_minted: Amount::from_attos(shares) }
+    }
+
+    async fn remove_liquidity_single(
+        &mut self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        share_amount: Amount,
+        token: BridgeToken,
+        min_amount: Amount,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(pool_key) = self.state.find_pool_key(&token_a, &token_b) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let other = match &pool_key {
+            (a, b) if *a == token => b.clone(),
+            (a, b) if *b == token => a.clone(),
+            _ => return DexResponse::Error(DexError::TokenNotInPool.to_string()),
+        };
+        if share_amount.is_zero() || self.get_shares(&user, &pool_key) < share_amount {
+            return DexResponse::Error(DexError::InsufficientShares.to_string());
+        }
+
+        // Quote the withdrawal and the swap before touching the pool
+        let quote = self.state.pools[&pool_key].quote_single_withdrawal(share_amount.to_attos(), &token);
+        let (kept, to_swap, swap_out) = match quote {
+            Ok(quote) => quote,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let received = Amount::from_attos(kept + swap_out);
+        if received < min_amount {
+            return DexResponse::Error(DexError::SlippageExceeded.to_string());
+        }
+
+        if let Err(error) = self.withdraw_liquidity(&user, &pool_key, share_amount) {
+            return DexResponse::Error(error.to_string());
+        }
+        if to_swap > 0 {
+            if let Err(error) = self.execute_swap(&user, &pool_key, &other, &token, Amount::from_attos(to_swap)) {
+                return DexResponse::Error(error.to_string());
+            }
+            let other_balance = self.get_user_balance(&user, &other);
+            self.set_user_balance(&user, &other, Amount::from_attos(other_balance.to_attos() - to_swap));
+            let token_balance = self.get_user_balance(&user, &token);
+            self.set_user_balance(&user, &token, Amount::from_attos(token_balance.to_attos() + swap_out));
+        }
+        self.match_limit_orders(&pool_key);
+
+        // Report amounts in the caller's token order
+        let (amount_a, amount_b) = if token == token_a { (received, Amount::ZERO) } else { (Amount::ZERO, received) };
+        DexResponse::LiquidityRemoved { amount_a, amount_b }
     }
 
     async fn remove_liquidity(
@@ -230,6 +908,708 @@ impl DexContract {
         token_b: BridgeToken,
         share_amount: Amount,
     ) -> DexResponse {
-        DexResponse::Error("Not implemented".to_string())
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(pool_key) = self.state.find_pool_key(&token_a, &token_b) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let (out_a, out_b) = match self.withdraw_liquidity(&user, &pool_key, share_amount) {
+            Ok(amounts) => amounts,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+
+        // Report amounts in the caller's token order
+        let (amount_a, amount_b) = if pool_key.0 == token_a { (out_a, out_b) } else { (out_b, out_a) };
+        DexResponse::LiquidityRemoved { amount_a, amount_b }
+    }
+
+    /// Burns `user`'s shares and pays out the proportional reserves, which is the
+    /// same for every curve
+    fn withdraw_liquidity(
+        &mut self,
+        user: &AccountOwner,
+        pool_key: &(BridgeToken, BridgeToken),
+        share_amount: Amount,
+    ) -> Result<(Amount, Amount), DexError> {
+        let user_shares = self.get_shares(user, pool_key);
+        if share_amount.is_zero() || user_shares < share_amount {
+            return Err(DexError::InsufficientShares);
+        }
+        let pool = self.state.pools.get(pool_key).ok_or(DexError::PoolNotFound)?;
+        let shares = share_amount.to_attos();
+        let (out_a, out_b) = pool.quote_withdrawal(shares)?;
+
+        self.record_price(pool_key);
+        self.update_rewards(pool_key);
+        let pool = self.state.pools.get_mut(pool_key).expect("pool exists");
+        pool.apply_withdrawal(shares, out_a, out_b);
+        self.set_shares(user, pool_key, Amount::from_attos(user_shares.to_attos() - shares));
+
+        let (token_a, token_b) = pool_key.clone();
+        let (amount_a, amount_b) = (Amount::from_attos(out_a), Amount::from_attos(out_b));
+        let balance_a = self.get_user_balance(user, &token_a);
+        let balance_b = self.get_user_balance(user, &token_b);
+        self.set_user_balance(user, &token_a, balance_a.saturating_add(amount_a));
+        self.set_user_balance(user, &token_b, balance_b.saturating_add(amount_b));
+
+        self.emit(DexEvent::Burn {
+            provider: *user,
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            shares: share_amount,
+        });
+        Ok((amount_a, amount_b))
+    }
+
+    async fn create_concentrated_pool(
+        &mut self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        fee_rate: u32,
+        tick_spacing: u32,
+        initial_price: Amount,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let pool_key = (token_a.clone(), token_b.clone());
+        if self.state.concentrated_pools.contains_key(&pool_key)
+            || self.state.concentrated_pools.contains_key(&(token_b.clone(), token_a.clone()))
+        {
+            return DexResponse::Error(DexError::PoolAlreadyExists.to_string());
+        }
+        let Some(sqrt_price) = concentrated::sqrt_price_from_price(initial_price) else {
+            return DexResponse::Error(RangeError::InvalidPrice.to_string());
+        };
+        let pool = match ConcentratedPool::new(token_a.clone(), token_b.clone(), fee_rate, tick_spacing, sqrt_price) {
+            Ok(pool) => pool,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+
+        self.state.concentrated_pools.insert(pool_key, pool);
+        self.emit(DexEvent::PoolCreated {
+            creator: user,
+            token_a,
+            token_b,
+            fee_rate,
+        });
+        DexResponse::PoolCreated { success: true }
+    }
+
+    fn find_concentrated_pool_key(&self, token_x: &BridgeToken, token_y: &BridgeToken) -> Option<(BridgeToken, BridgeToken)> {
+        [(token_x.clone(), token_y.clone()), (token_y.clone(), token_x.clone())]
+            .into_iter()
+            .find(|pool_key| self.state.concentrated_pools.contains_key(pool_key))
+    }
+
+    async fn mint_position(
+        &mut self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount_a: Amount,
+        amount_b: Amount,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(pool_key) = self.find_concentrated_pool_key(&token_a, &token_b) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let (amount_a, amount_b) = if pool_key.0 == token_a {
+            (amount_a, amount_b)
+        } else {
+            (amount_b, amount_a)
+        };
+
+        let mut pool = self.state.concentrated_pools[&pool_key].clone();
+        let mut position = Position {
+            id: self.state.next_position_id,
+            owner: user,
+            token_a: pool_key.0.clone(),
+            token_b: pool_key.1.clone(),
+            tick_lower,
+            tick_upper,
+            liquidity: Amount::ZERO,
+            tokens_owed_a: Amount::ZERO,
+            tokens_owed_b: Amount::ZERO,
+            fee_growth_inside_last_a: 0,
+            fee_growth_inside_last_b: 0,
+        };
+        let deposit = pool
+            .liquidity_for_amounts(tick_lower, tick_upper, amount_a.to_attos(), amount_b.to_attos())
+            .and_then(|liquidity| {
+                let delta = i128::try_from(liquidity).map_err(|_| RangeError::Overflow)?;
+                if delta == 0 {
+                    return Err(RangeError::InsufficientLiquidity);
+                }
+                pool.modify_position(&mut position, delta)
+            });
+        let (used_a, used_b) = match deposit {
+            Ok(amounts) => amounts,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+
+        // Rounding may ask for an atto more than offered, which the balance must cover
+        let (token_a, token_b) = pool_key.clone();
+        let balance_a = self.get_user_balance(&user, &token_a);
+        let balance_b = self.get_user_balance(&user, &token_b);
+        if balance_a.to_attos() < used_a || balance_b.to_attos() < used_b {
+            return DexResponse::Error(DexError::InsufficientBalance.to_string());
+        }
+        self.set_user_balance(&user, &token_a, Amount::from_attos(balance_a.to_attos() - used_a));
+        let balance_b = self.get_user_balance(&user, &token_b);
+        self.set_user_balance(&user, &token_b, Amount::from_attos(balance_b.to_attos() - used_b));
+
+        let position_id = position.id;
+        let liquidity = position.liquidity;
+        self.state.next_position_id += 1;
+        self.state.positions.insert(position_id, position);
+        self.state.concentrated_pools.insert(pool_key, pool);
+
+        let (amount_a, amount_b) = (Amount::from_attos(used_a), Amount::from_attos(used_b));
+        self.emit(DexEvent::Mint {
+            provider: user,
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            shares: liquidity,
+        });
+        DexResponse::PositionMinted { position_id, liquidity, amount_a, amount_b }
+    }
+
+    /// Looks up a position owned by the current user together with its pool
+    fn owned_position(&mut self, position_id: u64) -> Result<(AccountOwner, Position, ConcentratedPool), DexError> {
+        let user = self.get_user()?;
+        let position = self.state.positions.get(&position_id).ok_or(DexError::PositionNotFound)?;
+        if position.owner != user {
+            return Err(DexError::NotPositionOwner);
+        }
+        let pool = self
+            .state
+            .concentrated_pools
+            .get(&(position.token_a.clone(), position.token_b.clone()))
+            .ok_or(DexError::PoolNotFound)?;
+        Ok((user, position.clone(), pool.clone()))
+    }
+
+    async fn burn_position(&mut self, position_id: u64, liquidity: Option<Amount>) -> DexResponse {
+        let (user, mut position, mut pool) = match self.owned_position(position_id) {
+            Ok(found) => found,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let liquidity = liquidity.unwrap_or(position.liquidity);
+        if liquidity.is_zero() || liquidity > position.liquidity {
+            return DexResponse::Error(RangeError::InsufficientLiquidity.to_string());
+        }
+        let released = i128::try_from(liquidity.to_attos())
+            .map_err(|_| RangeError::Overflow)
+            .and_then(|delta| pool.modify_position(&mut position, -delta));
+        let (out_a, out_b) = match released {
+            Ok(amounts) => amounts,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+
+        let (fees_a, fees_b) = (position.tokens_owed_a, position.tokens_owed_b);
+        position.tokens_owed_a = Amount::ZERO;
+        position.tokens_owed_b = Amount::ZERO;
+        let (token_a, token_b) = (position.token_a.clone(), position.token_b.clone());
+        let (amount_a, amount_b) = (Amount::from_attos(out_a), Amount::from_attos(out_b));
+        let balance_a = self.get_user_balance(&user, &token_a);
+        let balance_b = self.get_user_balance(&user, &token_b);
+        self.set_user_balance(&user, &token_a, balance_a.saturating_add(amount_a).saturating_add(fees_a));
+        self.set_user_balance(&user, &token_b, balance_b.saturating_add(amount_b).saturating_add(fees_b));
+
+        self.state.positions.insert(position_id, position);
+        self.state.concentrated_pools.insert((token_a.clone(), token_b.clone()), pool);
+        self.emit(DexEvent::Burn {
+            provider: user,
+            token_a,
+            token_b,
+            amount_a,
+            amount_b,
+            shares: liquidity,
+        });
+        DexResponse::LiquidityRemoved { amount_a, amount_b }
+    }
+
+    async fn collect_fees(&mut self, position_id: u64) -> DexResponse {
+        let (user, mut position, pool) = match self.owned_position(position_id) {
+            Ok(found) => found,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        pool.accrue_fees(&mut position);
+        let (amount_a, amount_b) = (position.tokens_owed_a, position.tokens_owed_b);
+        position.tokens_owed_a = Amount::ZERO;
+        position.tokens_owed_b = Amount::ZERO;
+
+        let balance_a = self.get_user_balance(&user, &position.token_a);
+        let balance_b = self.get_user_balance(&user, &position.token_b);
+        self.set_user_balance(&user, &position.token_a, balance_a.saturating_add(amount_a));
+        self.set_user_balance(&user, &position.token_b, balance_b.saturating_add(amount_b));
+        self.state.positions.insert(position_id, position);
+        DexResponse::FeesCollected { amount_a, amount_b }
+    }
+
+    async fn transfer_position(&mut self, position_id: u64, recipient: AccountOwner) -> DexResponse {
+        let (_, mut position, _) = match self.owned_position(position_id) {
+            Ok(found) => found,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        position.owner = recipient;
+        self.state.positions.insert(position_id, position);
+        DexResponse::Ok
+    }
+
+    async fn swap_concentrated(
+        &mut self,
+        from_token: BridgeToken,
+        to_token: BridgeToken,
+        amount: Amount,
+        min_received: Amount,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        if self.get_user_balance(&user, &from_token) < amount {
+            return DexResponse::Error(DexError::InsufficientBalance.to_string());
+        }
+        let Some(pool_key) = self.find_concentrated_pool_key(&from_token, &to_token) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let a_to_b = from_token == pool_key.0;
+
+        // Swap on a copy so a failed swap leaves the pool untouched
+        let (pool, outcome) = match self.state.concentrated_pools[&pool_key].quote_swap(a_to_b, amount.to_attos()) {
+            Ok(result) => result,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let received = Amount::from_attos(outcome.amount_out);
+        if received < min_received {
+            return DexResponse::Error(DexError::SlippageExceeded.to_string());
+        }
+        self.state.concentrated_pools.insert(pool_key.clone(), pool);
+
+        let amount_in = Amount::from_attos(outcome.amount_in);
+        let from_balance = self.get_user_balance(&user, &from_token);
+        self.set_user_balance(&user, &from_token, Amount::from_attos(from_balance.to_attos() - outcome.amount_in));
+        let to_balance = self.get_user_balance(&user, &to_token);
+        self.set_user_balance(&user, &to_token, to_balance.saturating_add(received));
+
+        self.emit(DexEvent::Swap {
+            trader: user,
+            token_in: from_token.clone(),
+            token_out: to_token,
+            amount_in,
+            amount_out: received,
+        });
+        if outcome.fee > 0 {
+            self.emit(DexEvent::FeeCollected {
+                token_a: pool_key.0,
+                token_b: pool_key.1,
+                token: from_token,
+                amount: Amount::from_attos(outcome.fee),
+            });
+        }
+        DexResponse::SwapResult { received }
+    }
+
+    fn get_weighted_shares(&self, user: &AccountOwner, pool_id: u64) -> Amount {
+        self.state.weighted_shares
+            .get(&(*user, pool_id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn set_weighted_shares(&mut self, user: &AccountOwner, pool_id: u64, shares: Amount) {
+        self.state.weighted_shares.insert((*user, pool_id), shares);
+    }
+
+    async fn create_weighted_pool(
+        &mut self,
+        tokens: Vec<BridgeToken>,
+        weights: Vec<u32>,
+        amounts: Vec<Amount>,
+        fee_rate: u32,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let pool_id = self.state.next_weighted_pool_id;
+        let pool = match WeightedPool::new(pool_id, tokens.clone(), weights.clone(), amounts.clone(), fee_rate) {
+            Ok(pool) => pool,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        // Lock the first shares as for two-token pools
+        let total_shares = pool.total_shares.to_attos();
+        if total_shares <= math::MINIMUM_LIQUIDITY {
+            return DexResponse::Error(DexError::InsufficientInitialLiquidity.to_string());
+        }
+        let shares = Amount::from_attos(total_shares - math::MINIMUM_LIQUIDITY);
+        if tokens.iter().zip(&amounts).any(|(token, amount)| self.get_user_balance(&user, token) < *amount) {
+            return DexResponse::Error(DexError::InsufficientBalance.to_string());
+        }
+        for (token, amount) in tokens.iter().zip(&amounts) {
+            let balance = self.get_user_balance(&user, token);
+            self.set_user_balance(&user, token, Amount::from_attos(balance.to_attos() - amount.to_attos()));
+        }
+
+        self.set_weighted_shares(&user, pool_id, shares);
+        self.state.weighted_pools.insert(pool_id, pool);
+        self.state.next_weighted_pool_id += 1;
+
+        self.emit(DexEvent::WeightedPoolCreated {
+            creator: user,
+            pool_id,
+            tokens,
+            weights,
+            fee_rate,
+        });
+        self.emit(DexEvent::WeightedJoin {
+            provider: user,
+            pool_id,
+            amounts,
+            shares,
+        });
+        DexResponse::WeightedPoolCreated { pool_id, shares }
+    }
+
+    async fn join_weighted_pool(&mut self, pool_id: u64, token: BridgeToken, amount: Amount, min_shares: Amount) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(pool) = self.state.weighted_pools.get(&pool_id) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let minted = pool.index_of(&token).and_then(|index| Ok((index, pool.quote_join(&token, amount.to_attos())?)));
+        let (index, shares) = match minted {
+            Ok((index, shares)) => (index, Amount::from_attos(shares)),
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        if shares.is_zero() || shares < min_shares {
+            return DexResponse::Error(DexError::SlippageExceeded.to_string());
+        }
+        let balance = self.get_user_balance(&user, &token);
+        if balance < amount {
+            return DexResponse::Error(DexError::InsufficientBalance.to_string());
+        }
+
+        self.set_user_balance(&user, &token, Amount::from_attos(balance.to_attos() - amount.to_attos()));
+        let user_shares = self.get_weighted_shares(&user, pool_id);
+        self.set_weighted_shares(&user, pool_id, user_shares.saturating_add(shares));
+        let pool = self.state.weighted_pools.get_mut(&pool_id).expect("pool checked above");
+        pool.adjust_balance(index, amount.to_attos(), true);
+        pool.total_shares = pool.total_shares.saturating_add(shares);
+
+        let mut amounts = vec![Amount::ZERO; pool.assets.len()];
+        amounts[index] = amount;
+        self.emit(DexEvent::WeightedJoin {
+            provider: user,
+            pool_id,
+            amounts,
+            shares,
+        });
+        DexResponse::LiquidityAdded { shares_minted: shares }
+    }
+
+    async fn exit_weighted_pool(
+        &mut self,
+        pool_id: u64,
+        shares: Amount,
+        token: Option<BridgeToken>,
+        min_amount: Amount,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let user_shares = self.get_weighted_shares(&user, pool_id);
+        if shares.is_zero() || user_shares < shares {
+            return DexResponse::Error(DexError::InsufficientShares.to_string());
+        }
+        let Some(pool) = self.state.weighted_pools.get(&pool_id) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let payout = match &token {
+            Some(token) => pool.index_of(token).and_then(|index| {
+                let mut amounts = vec![0; pool.assets.len()];
+                amounts[index] = pool.quote_exit(token, shares.to_attos())?;
+                Ok(amounts)
+            }),
+            None => pool.quote_proportional_exit(shares.to_attos()),
+        };
+        let amounts = match payout {
+            Ok(amounts) => amounts.into_iter().map(Amount::from_attos).collect::<Vec<_>>(),
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        if token.is_some() {
+            let paid = amounts.iter().copied().max().unwrap_or_default();
+            if paid.is_zero() || paid < min_amount {
+                return DexResponse::Error(DexError::SlippageExceeded.to_string());
+            }
+        }
+
+        let pool = self.state.weighted_pools.get_mut(&pool_id).expect("pool checked above");
+        pool.total_shares = Amount::from_attos(pool.total_shares.to_attos() - shares.to_attos());
+        let tokens = pool.tokens();
+        for (index, amount) in amounts.iter().enumerate() {
+            pool.adjust_balance(index, amount.to_attos(), false);
+        }
+        self.set_weighted_shares(&user, pool_id, Amount::from_attos(user_shares.to_attos() - shares.to_attos()));
+        for (token, amount) in tokens.iter().zip(&amounts) {
+            let balance = self.get_user_balance(&user, token);
+            self.set_user_balance(&user, token, balance.saturating_add(*amount));
+        }
+
+        self.emit(DexEvent::WeightedExit {
+            provider: user,
+            pool_id,
+            amounts: amounts.clone(),
+            shares,
+        });
+        DexResponse::LiquidityWithdrawn { amounts }
+    }
+
+    async fn swap_weighted(
+        &mut self,
+        pool_id: u64,
+        from_token: BridgeToken,
+        to_token: BridgeToken,
+        amount: Amount,
+        min_received: Amount,
+    ) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let balance = self.get_user_balance(&user, &from_token);
+        if balance < amount {
+            return DexResponse::Error(DexError::InsufficientBalance.to_string());
+        }
+        let Some(pool) = self.state.weighted_pools.get_mut(&pool_id) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let quote = pool
+            .quote_swap(&from_token, &to_token, amount.to_attos())
+            .and_then(|(amount_out, _)| Ok((pool.index_of(&from_token)?, pool.index_of(&to_token)?, amount_out)));
+        let (index_in, index_out, amount_out) = match quote {
+            Ok(quote) => quote,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let received = Amount::from_attos(amount_out);
+        if received < min_received {
+            return DexResponse::Error(DexError::SlippageExceeded.to_string());
+        }
+
+        // The fee stays in the pool, accruing to its shareholders
+        pool.adjust_balance(index_in, amount.to_attos(), true);
+        pool.adjust_balance(index_out, amount_out, false);
+        self.set_user_balance(&user, &from_token, Amount::from_attos(balance.to_attos() - amount.to_attos()));
+        let to_balance = self.get_user_balance(&user, &to_token);
+        self.set_user_balance(&user, &to_token, to_balance.saturating_add(received));
+
+        self.emit(DexEvent::Swap {
+            trader: user,
+            token_in: from_token,
+            token_out: to_token,
+            amount_in: amount,
+            amount_out: received,
+        });
+        DexResponse::SwapResult { received }
+    }
+
+    async fn create_reward_program(
+        &mut self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        reward_token: BridgeToken,
+        amount: Amount,
+        duration_secs: u64,
+    ) -> DexResponse {
+        let user = match self.require_admin() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(pool_key) = self.state.find_pool_key(&token_a, &token_b) else {
+            return DexResponse::Error(DexError::PoolNotFound.to_string());
+        };
+        let now = self.runtime.system_time().micros();
+        let program_id = self.state.next_reward_program_id;
+        let program = RewardProgram::new(program_id, pool_key, reward_token, user, now);
+        self.state.reward_programs.insert(program_id, program);
+        if let Err(error) = self.refill_reward_program(program_id, amount, duration_secs) {
+            self.state.reward_programs.remove(&program_id);
+            return DexResponse::Error(error.to_string());
+        }
+        self.state.next_reward_program_id += 1;
+        DexResponse::RewardProgramCreated { program_id }
+    }
+
+    async fn fund_reward_program(&mut self, program_id: u64, amount: Amount, duration_secs: u64) -> DexResponse {
+        match self.refill_reward_program(program_id, amount, duration_secs) {
+            Ok(()) => DexResponse::Ok,
+            Err(error) => DexResponse::Error(error.to_string()),
+        }
+    }
+
+    /// Takes `amount` of the reward token from the funder and restarts emission
+    fn refill_reward_program(&mut self, program_id: u64, amount: Amount, duration_secs: u64) -> Result<(), DexError> {
+        let user = self.require_admin()?;
+        let program = self.state.reward_programs.get(&program_id).ok_or(DexError::RewardProgramNotFound)?;
+        if amount.is_zero() || duration_secs == 0 {
+            return Err(DexError::InvalidRewardProgram);
+        }
+        let reward_token = program.reward_token.clone();
+        let pool_key = program.pool_key();
+        let balance = self.get_user_balance(&user, &reward_token);
+        if balance < amount {
+            return Err(DexError::InsufficientBalance);
+        }
+        self.set_user_balance(&user, &reward_token, Amount::from_attos(balance.to_attos() - amount.to_attos()));
+
+        self.update_rewards(&pool_key);
+        let now = self.runtime.system_time().micros();
+        let program = self.state.reward_programs.get_mut(&program_id).expect("program checked above");
+        program.refill(now, amount, duration_secs);
+        let end_time = program.end_time;
+        self.log_admin_action(
+            &user,
+            AdminActionKind::FundRewardProgram,
+            format!("program {program_id}: {amount} {}, ending at {end_time}", reward_token.symbol),
+        );
+        self.emit(DexEvent::RewardProgramFunded {
+            funder: user,
+            program_id,
+            reward_token,
+            amount,
+            end_time,
+        });
+        Ok(())
+    }
+
+    async fn claim_rewards(&mut self, program_id: u64) -> DexResponse {
+        let user = match self.get_user() {
+            Ok(user) => user,
+            Err(error) => return DexResponse::Error(error.to_string()),
+        };
+        let Some(program) = self.state.reward_programs.get(&program_id) else {
+            return DexResponse::Error(DexError::RewardProgramNotFound.to_string());
+        };
+        let (pool_key, reward_token) = (program.pool_key(), program.reward_token.clone());
+        self.settle_rewards(&user, &pool_key);
+        let stake = self.state.reward_stakes.entry((program_id, user)).or_default();
+        let amount = std::mem::take(&mut stake.pending);
+
+        let balance = self.get_user_balance(&user, &reward_token);
+        self.set_user_balance(&user, &reward_token, balance.saturating_add(amount));
+        self.emit(DexEvent::RewardsClaimed {
+            user,
+            program_id,
+            reward_token,
+            amount,
+        });
+        DexResponse::RewardsClaimed { amount }
+    }
+
+    /// The owner counts as an admin
+    fn require_admin(&mut self) -> Result<AccountOwner, DexError> {
+        let user = self.get_user()?;
+        self.state.require_admin(&user)?;
+        Ok(user)
+    }
+
+    fn log_admin_action(&mut self, actor: &AccountOwner, action: AdminActionKind, detail: String) {
+        let now = self.runtime.system_time().micros();
+        let entry = self.state.log_admin_action(now, *actor, action, detail);
+        self.emit_admin_action(entry);
+    }
+
+    fn emit_admin_action(&mut self, entry: AdminAction) {
+        self.emit(DexEvent::AdminAction {
+            actor: entry.actor,
+            action: entry.action,
+            detail: entry.detail,
+        });
+    }
+
+    /// Publishes a logged admin action, or reports why it was refused
+    fn admin_response(&mut self, result: Result<AdminAction, DexError>) -> DexResponse {
+        match result {
+            Ok(entry) => {
+                self.emit_admin_action(entry);
+                DexResponse::Ok
+            }
+            Err(error) => DexResponse::Error(error.to_string()),
+        }
+    }
+
+    async fn add_admin(&mut self, admin: AccountOwner) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self.get_user().and_then(|user| self.state.add_admin(user, admin, now));
+        self.admin_response(result)
+    }
+
+    async fn remove_admin(&mut self, admin: AccountOwner) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self.get_user().and_then(|user| self.state.remove_admin(user, admin, now));
+        self.admin_response(result)
+    }
+
+    async fn renounce_admin(&mut self) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self.get_user().and_then(|user| self.state.renounce_admin(user, now));
+        self.admin_response(result)
+    }
+
+    async fn transfer_ownership(&mut self, new_owner: AccountOwner) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self.get_user().and_then(|user| self.state.transfer_ownership(user, new_owner, now));
+        self.admin_response(result)
+    }
+
+    async fn renounce_ownership(&mut self) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self.get_user().and_then(|user| self.state.renounce_ownership(user, now));
+        self.admin_response(result)
+    }
+
+    async fn set_pool_fee(&mut self, token_a: BridgeToken, token_b: BridgeToken, fee_rate: u32) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self
+            .get_user()
+            .and_then(|user| self.state.set_pool_fee(user, &token_a, &token_b, fee_rate, now));
+        self.admin_response(result)
+    }
+
+    async fn set_paused(&mut self, paused: bool) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self.get_user().and_then(|user| self.state.set_paused(user, paused, now));
+        self.admin_response(result)
+    }
+
+    async fn set_bridge_tracker_app(&mut self, app: Option<ApplicationId>) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self.get_user().and_then(|user| self.state.set_bridge_tracker_app(user, app, now));
+        self.admin_response(result)
+    }
+
+    async fn set_trusted_minter(&mut self, network: String, minter: Option<ApplicationId>) -> DexResponse {
+        let now = self.runtime.system_time().micros();
+        let result = self
+            .get_user()
+            .and_then(|user| self.state.set_trusted_minter(user, network, minter, now));
+        self.admin_response(result)
     }
 }
+
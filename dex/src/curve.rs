@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{cpmm_output, mul_div, product_at_least, swap_fee, FEE_DENOMINATOR},
+    oracle::PRICE_SCALE,
+};
+
+/// Iteration cap for the StableSwap Newton solvers
+const MAX_ITERATIONS: usize = 255;
+/// Largest accepted StableSwap amplification
+pub const MAX_AMPLIFICATION: u64 = 1_000_000;
+
+/// Pricing curve of a pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Curve {
+    /// x * y = k
+    #[default]
+    ConstantProduct,
+    /// Curve-style invariant for pegged pairs; higher amplification is flatter around 1:1
+    StableSwap { amplification: u64 },
+}
+
+async_graphql::scalar!(Curve);
+
+impl Curve {
+    /// Output for `amount_in` (already net of fees) against the given reserves
+    pub fn output(&self, input_reserve: u128, output_reserve: u128, amount_in: u128) -> Option<u128> {
+        match *self {
+            Curve::ConstantProduct => cpmm_output(input_reserve, output_reserve, amount_in),
+            Curve::StableSwap { amplification } => {
+                let ann = stable_ann(amplification)?;
+                let d = stable_invariant(ann, input_reserve, output_reserve)?;
+                let new_output = stable_reserve(ann, input_reserve.checked_add(amount_in)?, d)?;
+                // Round against the trader
+                output_reserve.checked_sub(new_output)?.checked_sub(1)
+            }
+        }
+    }
+
+    /// Marginal price of one unit of `base` in units of `quote`, scaled by `PRICE_SCALE`
+    pub fn spot_price(&self, base_reserve: u128, quote_reserve: u128) -> u128 {
+        if base_reserve == 0 || quote_reserve == 0 {
+            return 0;
+        }
+        match *self {
+            Curve::ConstantProduct => {
+                mul_div(quote_reserve, PRICE_SCALE, base_reserve).unwrap_or(u128::MAX)
+            }
+            Curve::StableSwap { amplification } => {
+                stable_spot_price(amplification, base_reserve, quote_reserve).unwrap_or(u128::MAX)
+            }
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Curve::ConstantProduct => true,
+            Curve::StableSwap { amplification } => (1..=MAX_AMPLIFICATION).contains(&amplification),
+        }
+    }
+
+    /// Checks that reserves after a flash swap still satisfy the curve's invariant
+    /// once the fee on whatever was paid in is set aside
+    pub fn flash_repaid(
+        &self,
+        (old_a, old_b): (u128, u128),
+        (out_a, out_b): (u128, u128),
+        (new_a, new_b): (u128, u128),
+        fee_rate: u32,
+    ) -> bool {
+        let paid_a = new_a.saturating_sub(old_a.saturating_sub(out_a));
+        let paid_b = new_b.saturating_sub(old_b.saturating_sub(out_b));
+        let adjusted_a = new_a - swap_fee(paid_a, fee_rate);
+        let adjusted_b = new_b - swap_fee(paid_b, fee_rate);
+        match self.invariant(adjusted_a, adjusted_b).zip(self.invariant(old_a, old_b)) {
+            Some((adjusted, old)) => adjusted >= old,
+            None => product_at_least(adjusted_a, adjusted_b, old_a, old_b),
+        }
+    }
+
+    /// Pool size measure used for share accounting: the StableSwap invariant `D`,
+    /// or `None` for constant product pools, which account proportionally
+    pub fn invariant(&self, reserve_a: u128, reserve_b: u128) -> Option<u128> {
+        match *self {
+            Curve::ConstantProduct => None,
+            Curve::StableSwap { amplification } => {
+                stable_invariant(stable_ann(amplification)?, reserve_a, reserve_b)
+            }
+        }
+    }
+}
+
+/// `A * n^n` for a two-token pool
+fn stable_ann(amplification: u64) -> Option<u128> {
+    if amplification == 0 {
+        return None;
+    }
+    u128::from(amplification).checked_mul(4)
+}
+
+/// `D^3 / (4xy)`, the product term of the invariant
+fn stable_product_term(d: u128, x: u128, y: u128) -> Option<u128> {
+    mul_div(mul_div(d, d, x.checked_mul(2)?)?, d, y.checked_mul(2)?)
+}
+
+/// Solves `Ann*(x + y) + D = Ann*D + D^3 / (4xy)` for `D` by Newton's method
+pub fn stable_invariant(ann: u128, x: u128, y: u128) -> Option<u128> {
+    let sum = x.checked_add(y)?;
+    if sum == 0 {
+        return Some(0);
+    }
+    if x == 0 || y == 0 {
+        return None;
+    }
+
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        let d_p = stable_product_term(d, x, y)?;
+        let previous = d;
+        let numerator = ann.checked_mul(sum)?.checked_add(d_p.checked_mul(2)?)?;
+        let denominator = (ann - 1).checked_mul(d)?.checked_add(d_p.checked_mul(3)?)?;
+        d = mul_div(numerator, d, denominator)?;
+        if d.abs_diff(previous) <= 1 {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Solves the invariant for the other reserve when one side becomes `x`
+pub fn stable_reserve(ann: u128, x: u128, d: u128) -> Option<u128> {
+    if x == 0 {
+        return None;
+    }
+    // y^2 + (b - D)y = c, with b = x + D/Ann and c = D^3 / (4x * Ann).
+    // `c` itself can exceed u128, so it is only ever divided through.
+    let d_squared_over_2x = mul_div(d, d, x.checked_mul(2)?)?;
+    let b = x.checked_add(d / ann)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let previous = y;
+        let denominator = y.checked_mul(2)?.checked_add(b)?.checked_sub(d)?;
+        let c_term = mul_div(d_squared_over_2x, d, ann.checked_mul(2)?.checked_mul(denominator)?)?;
+        y = mul_div(y, y, denominator)?.checked_add(c_term)?;
+        if y.abs_diff(previous) <= 1 {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// dy/dx on the StableSwap curve: (Ann + D_P/x) / (Ann + D_P/y)
+fn stable_spot_price(amplification: u64, base_reserve: u128, quote_reserve: u128) -> Option<u128> {
+    let ann = stable_ann(amplification)?;
+    let d = stable_invariant(ann, base_reserve, quote_reserve)?;
+    let d_p = stable_product_term(d, base_reserve, quote_reserve)?;
+    let scaled_ann = ann.checked_mul(PRICE_SCALE)?;
+    let numerator = scaled_ann.checked_add(mul_div(d_p, PRICE_SCALE, base_reserve)?)?;
+    let denominator = scaled_ann.checked_add(mul_div(d_p, PRICE_SCALE, quote_reserve)?)?;
+    mul_div(numerator, PRICE_SCALE, denominator)
+}
+
+/// Reserves after a StableSwap deposit, less the fee charged on the part of
+/// the deposit that moves the pool away from its current balance
+pub fn stable_deposit_after_fees(
+    (old_a, old_b): (u128, u128),
+    (new_a, new_b): (u128, u128),
+    d0: u128,
+    d1: u128,
+    fee_rate: u32,
+) -> Option<(u128, u128)> {
+    // Two-token imbalance fee is half the swap fee, as in Curve
+    let charge = |old: u128, new: u128| -> Option<u128> {
+        let ideal = mul_div(d1, old, d0)?;
+        let imbalance = ideal.abs_diff(new);
+        let fee = mul_div(imbalance, u128::from(fee_rate), 2 * FEE_DENOMINATOR)?;
+        new.checked_sub(fee)
+    };
+    Some((charge(old_a, new_a)?, charge(old_b, new_b)?))
+}
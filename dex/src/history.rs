@@ -0,0 +1,103 @@
+use async_graphql::{Enum, SimpleObject};
+use linera_sdk::linera_base_types::{AccountOwner, Amount};
+use serde::{Deserialize, Serialize};
+
+use crate::{math::mul_div, oracle::PRICE_SCALE, BridgeToken};
+
+/// Maximum number of trades returned by a single page
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Enum)]
+pub enum SwapDirection {
+    AToB, // token_a in, token_b out
+    BToA, // token_b in, token_a out
+}
+
+// A single executed swap
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct Trade {
+    pub timestamp: u64, // Block time in microseconds
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub direction: SwapDirection,
+    pub amount_in: Amount,
+    pub amount_out: Amount,
+    pub price: Amount, // Execution price of token_a in token_b
+    pub trader: AccountOwner,
+}
+
+// OHLCV bucket over a fixed interval
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, SimpleObject)]
+pub struct Candle {
+    pub start: u64, // Bucket start in microseconds
+    pub open: Amount,
+    pub high: Amount,
+    pub low: Amount,
+    pub close: Amount,
+    pub volume_a: Amount,
+    pub volume_b: Amount,
+    pub trades: u32,
+}
+
+impl Trade {
+    /// Amounts of token_a and token_b that changed hands
+    pub fn volumes(&self) -> (Amount, Amount) {
+        match self.direction {
+            SwapDirection::AToB => (self.amount_in, self.amount_out),
+            SwapDirection::BToA => (self.amount_out, self.amount_in),
+        }
+    }
+}
+
+/// Price of token_a in token_b implied by a swap's amounts
+pub fn execution_price(direction: SwapDirection, amount_in: Amount, amount_out: Amount) -> Amount {
+    let (amount_a, amount_b) = match direction {
+        SwapDirection::AToB => (amount_in, amount_out),
+        SwapDirection::BToA => (amount_out, amount_in),
+    };
+    if amount_a.is_zero() {
+        return Amount::ZERO;
+    }
+    Amount::from_attos(mul_div(amount_b.to_attos(), PRICE_SCALE, amount_a.to_attos()).unwrap_or(u128::MAX))
+}
+
+/// Trades with `from <= timestamp < to`; the log is ordered by timestamp
+pub fn trades_in_range(trades: &[Trade], from: u64, to: u64) -> &[Trade] {
+    let start = trades.partition_point(|trade| trade.timestamp < from);
+    let end = trades.partition_point(|trade| trade.timestamp < to);
+    &trades[start..end.max(start)]
+}
+
+/// Aggregates trades into candles of `interval` microseconds, skipping empty buckets
+pub fn candles(trades: &[Trade], interval: u64) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = Vec::new();
+    if interval == 0 {
+        return candles;
+    }
+
+    for trade in trades {
+        let start = trade.timestamp - trade.timestamp % interval;
+        let (volume_a, volume_b) = trade.volumes();
+        match candles.last_mut() {
+            Some(candle) if candle.start == start => {
+                candle.high = candle.high.max(trade.price);
+                candle.low = candle.low.min(trade.price);
+                candle.close = trade.price;
+                candle.volume_a = candle.volume_a.saturating_add(volume_a);
+                candle.volume_b = candle.volume_b.saturating_add(volume_b);
+                candle.trades += 1;
+            }
+            _ => candles.push(Candle {
+                start,
+                open: trade.price,
+                high: trade.price,
+                low: trade.price,
+                close: trade.price,
+                volume_a,
+                volume_b,
+                trades: 1,
+            }),
+        }
+    }
+    candles
+}
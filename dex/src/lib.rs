@@ -1,10 +1,32 @@
 use async_graphql::{InputObject, Request, Response, SimpleObject};
 use linera_sdk::{
     graphql::GraphQLMutationRoot,
-    linera_base_types::{Amount, ApplicationId, ChainId, ContractAbi, ServiceAbi},
+    linera_base_types::{AccountOwner, Amount, ApplicationId, ChainId, ContractAbi, ServiceAbi},
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+pub mod admin;
+pub mod concentrated;
+pub mod curve;
+pub mod history;
+pub mod math;
+pub mod oracle;
+pub mod orders;
+pub mod rewards;
+pub mod stats;
+pub mod weighted;
+
+use admin::{AdminAction, AdminActionKind};
+use concentrated::{ConcentratedPool, Position, RangeError};
+use curve::Curve;
+use history::Trade;
+use orders::{LimitOrder, OrderSide};
+use oracle::{PriceObservation, Twap};
+use rewards::{RewardProgram, RewardStake};
+use stats::PoolVolume;
+use weighted::{WeightedError, WeightedPool};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DexAbi;
@@ -19,6 +41,48 @@ impl ServiceAbi for DexAbi {
     type QueryResponse = Response;
 }
 
+/// ABI a flash swap receiver implements. The DEX calls it with `FlashSwapCallback`
+/// after crediting the borrowed output, and debits the returned repayment.
+///
+/// The DEX is still executing during the callback, so the receiver cannot call back
+/// into it. Instead it spends the output through the returned `swaps`, which the DEX
+/// runs from the receiver's balances in other pools before taking the repayment, e.g.
+/// to close an arbitrage loop. Anyone may name an application as the receiver, so it
+/// should check `initiator` and abort when it doesn't trust them.
+pub struct FlashBorrowerAbi;
+
+impl ContractAbi for FlashBorrowerAbi {
+    type Operation = FlashSwapCallback;
+    type Response = FlashSwapRepayment;
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FlashSwapCallback {
+    pub initiator: AccountOwner, // Who requested the flash swap
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub amount_a_out: Amount,
+    pub amount_b_out: Amount,
+    pub data: Vec<u8>,
+}
+
+/// Amounts the DEX may take from the receiver's balances to repay a flash swap
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FlashSwapRepayment {
+    pub amount_a: Amount,
+    pub amount_b: Amount,
+    #[serde(default)]
+    pub swaps: Vec<FlashSwapLeg>, // Run in order before the repayment is taken
+}
+
+/// A swap the DEX runs from the flash swap receiver's balances
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FlashSwapLeg {
+    pub from_token: BridgeToken,
+    pub to_token: BridgeToken,
+    pub amount: Amount,
+}
+
 // Bridge token identifiers
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize, SimpleObject, InputObject)]
 #[graphql(input_name = "BridgeTokenInput")]
@@ -27,6 +91,37 @@ pub struct BridgeToken {
     pub network: String,     // "sepolia", "arbitrum-sepolia"
 }
 
+/// Symbol of the base token that all pairs trade against and values are quoted in
+pub const BASE_TOKEN_SYMBOL: &str = "wUSDC";
+
+/// Network tag used for tokens created inside the DEX rather than bridged in
+pub const NATIVE_NETWORK: &str = "linera";
+
+impl BridgeToken {
+    /// Identifier of a DEX-native token with the given symbol
+    pub fn native(symbol: &str) -> Self {
+        BridgeToken {
+            symbol: symbol.to_string(),
+            network: NATIVE_NETWORK.to_string(),
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.network == NATIVE_NETWORK
+    }
+}
+
+// User-created token metadata
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct TradingToken {
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+    pub total_supply: Amount,
+    pub creator: AccountOwner,
+    pub mintable: bool, // Creator can mint more after launch
+}
+
 #[derive(Debug, Deserialize, Serialize, GraphQLMutationRoot)]
 pub enum DexOperation {
     SwapTokens {
@@ -40,6 +135,7 @@ pub enum DexOperation {
         amount_a: Amount,
         amount_b: Amount,
         fee_rate: u32,
+        curve: Option<Curve>, // Defaults to constant product
     },
     AddLiquidity {
         token_a: BridgeToken,
@@ -52,17 +148,223 @@ pub enum DexOperation {
         token_b: BridgeToken,
         share_amount: Amount,
     },
+    /// Add liquidity from one token, swapping the right part of it for the other side first
+    AddLiquiditySingle {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        token: BridgeToken,
+        amount: Amount,
+        min_shares: Amount,
+    },
+    /// Remove liquidity and swap the other side, paying out only `token`
+    RemoveLiquiditySingle {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        share_amount: Amount,
+        token: BridgeToken,
+        min_amount: Amount,
+    },
     // Bridge integration operations
     MintBridgeToken {
         token: BridgeToken,
-        user: String,
+        user: AccountOwner,
         amount: Amount,
     },
     BurnBridgeToken {
         token: BridgeToken,
-        user: String,
+        user: AccountOwner,
+        amount: Amount,
+    },
+    // Custom token operations
+    CreateToken {
+        symbol: String,
+        name: String,
+        decimals: u8,
+        initial_supply: Amount,
+        mintable: bool,
+    },
+    MintToken {
+        symbol: String,
+        recipient: AccountOwner,
         amount: Amount,
     },
+    /// Create a token and open a pool against `quote_token` in one step
+    LaunchToken {
+        symbol: String,
+        name: String,
+        decimals: u8,
+        initial_supply: Amount,
+        mintable: bool,
+        quote_token: BridgeToken,
+        token_amount: Amount,
+        quote_amount: Amount,
+        fee_rate: u32,
+    },
+    /// Read the time-weighted average price of the pool trading the two tokens, in either
+    /// order; the result names the pool's own token order. Intended for `call_application`
+    GetTwap {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        window_secs: u64,
+    },
+    /// Escrow `amount_in` and fill against the pool once the price reaches `min_price`
+    PlaceLimitOrder {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        side: OrderSide,
+        amount_in: Amount,
+        min_price: Amount,
+        expiry: Option<u64>,
+    },
+    CancelLimitOrder {
+        order_id: u64,
+    },
+    /// Lend pool output to `receiver`, call it back, and require repayment with fees.
+    /// Linera forbids reentrant calls, so `receiver` must not be the calling application.
+    FlashSwap {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        amount_a_out: Amount,
+        amount_b_out: Amount,
+        receiver: ApplicationId,
+        data: Vec<u8>,
+    },
+    // Concentrated liquidity operations
+    CreateConcentratedPool {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        fee_rate: u32,
+        tick_spacing: u32,
+        initial_price: Amount, // Price of token_a in token_b
+    },
+    MintPosition {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount_a: Amount,
+        amount_b: Amount,
+    },
+    /// Remove liquidity from a position (all of it if `liquidity` is omitted) and collect its fees
+    BurnPosition {
+        position_id: u64,
+        liquidity: Option<Amount>,
+    },
+    CollectFees {
+        position_id: u64,
+    },
+    TransferPosition {
+        position_id: u64,
+        recipient: AccountOwner,
+    },
+    SwapConcentrated {
+        from_token: BridgeToken,
+        to_token: BridgeToken,
+        amount: Amount,
+        min_received: Amount,
+    },
+    // Weighted pool operations
+    CreateWeightedPool {
+        tokens: Vec<BridgeToken>,
+        weights: Vec<u32>, // Basis points, summing to 10000
+        amounts: Vec<Amount>,
+        fee_rate: u32,
+    },
+    /// Deposit a single token for pool shares
+    JoinWeightedPool {
+        pool_id: u64,
+        token: BridgeToken,
+        amount: Amount,
+        min_shares: Amount,
+    },
+    /// Burn shares for a single token, or for every token proportionally if `token` is omitted
+    ExitWeightedPool {
+        pool_id: u64,
+        shares: Amount,
+        token: Option<BridgeToken>,
+        min_amount: Amount, // Only applies to single-token exits
+    },
+    SwapWeighted {
+        pool_id: u64,
+        from_token: BridgeToken,
+        to_token: BridgeToken,
+        amount: Amount,
+        min_received: Amount,
+    },
+    // Liquidity mining operations
+    /// Fund a reward token emitted to a pool's share holders over `duration_secs`
+    CreateRewardProgram {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        reward_token: BridgeToken,
+        amount: Amount,
+        duration_secs: u64,
+    },
+    /// Add rewards and restart emission of everything unemitted over `duration_secs`
+    FundRewardProgram {
+        program_id: u64,
+        amount: Amount,
+        duration_secs: u64,
+    },
+    ClaimRewards {
+        program_id: u64,
+    },
+    // Administration; ownership and the admin set are managed by the owner
+    AddAdmin {
+        admin: AccountOwner,
+    },
+    RemoveAdmin {
+        admin: AccountOwner,
+    },
+    /// Give up the caller's own admin role
+    RenounceAdmin,
+    TransferOwnership {
+        new_owner: AccountOwner,
+    },
+    /// Leave the DEX without an owner; admins keep their roles
+    RenounceOwnership,
+    SetPoolFee {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        fee_rate: u32,
+    },
+    /// Halt swaps and new liquidity, including single-sided withdrawals since they swap
+    /// through the pool; plain withdrawals and claims stay open
+    SetPaused {
+        paused: bool,
+    },
+    /// Replace the bridge tracker that mints for networks without their own minter
+    SetBridgeTrackerApp {
+        app: Option<ApplicationId>,
+    },
+    /// Authorize `minter` to mint and burn bridged tokens of one network, or revoke it
+    SetTrustedMinter {
+        network: String,
+        minter: Option<ApplicationId>,
+    },
+}
+
+impl DexOperation {
+    /// Whether the operation is refused while the DEX is paused
+    pub fn is_halted_by_pause(&self) -> bool {
+        matches!(
+            self,
+            DexOperation::SwapTokens { .. }
+                | DexOperation::CreatePool { .. }
+                | DexOperation::AddLiquidity { .. }
+                | DexOperation::LaunchToken { .. }
+                | DexOperation::PlaceLimitOrder { .. }
+                | DexOperation::FlashSwap { .. }
+                | DexOperation::CreateConcentratedPool { .. }
+                | DexOperation::MintPosition { .. }
+                | DexOperation::SwapConcentrated { .. }
+                | DexOperation::CreateWeightedPool { .. }
+                | DexOperation::JoinWeightedPool { .. }
+                | DexOperation::SwapWeighted { .. }
+                | DexOperation::AddLiquiditySingle { .. }
+                | DexOperation::RemoveLiquiditySingle { .. }
+        )
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
@@ -83,9 +385,220 @@ pub enum DexResponse {
         amount_a: Amount,
         amount_b: Amount,
     },
+    TokenCreated {
+        token: BridgeToken,
+    },
+    Twap(Twap),
+    OrderPlaced {
+        order_id: u64,
+    },
+    PositionMinted {
+        position_id: u64,
+        liquidity: Amount,
+        amount_a: Amount,
+        amount_b: Amount,
+    },
+    FeesCollected {
+        amount_a: Amount,
+        amount_b: Amount,
+    },
+    WeightedPoolCreated {
+        pool_id: u64,
+        shares: Amount,
+    },
+    /// Tokens paid out of a weighted pool, in the pool's token order
+    LiquidityWithdrawn {
+        amounts: Vec<Amount>,
+    },
+    RewardProgramCreated {
+        program_id: u64,
+    },
+    RewardsClaimed {
+        amount: Amount,
+    },
     Error(String),
 }
 
+#[derive(Debug, Error)]
+pub enum DexError {
+    #[error("Insufficient balance")]
+    InsufficientBalance,
+    #[error("Pool does not exist")]
+    PoolNotFound,
+    #[error("Pool already exists")]
+    PoolAlreadyExists,
+    #[error("Pool tokens must differ")]
+    IdenticalTokens,
+    #[error("Invalid pool state")]
+    InvalidPoolState,
+    #[error("Insufficient pool reserves")]
+    InsufficientPoolReserves,
+    #[error("Invalid calculation")]
+    InvalidCalculation,
+    #[error("Pool has no shares")]
+    NoPoolShares,
+    #[error("Zero reserve")]
+    ZeroReserve,
+    #[error("Insufficient output reserve")]
+    InsufficientOutputReserve,
+    #[error("Token already exists")]
+    TokenAlreadyExists,
+    #[error("Token does not exist")]
+    TokenNotFound,
+    #[error("Invalid token metadata")]
+    InvalidTokenMetadata,
+    #[error("Token supply is fixed")]
+    TokenNotMintable,
+    #[error("Only the token creator can do this")]
+    NotTokenCreator,
+    #[error("Invalid order")]
+    InvalidOrder,
+    #[error("Order does not exist")]
+    OrderNotFound,
+    #[error("Only the order owner can do this")]
+    NotOrderOwner,
+    #[error("Order is not open")]
+    OrderNotOpen,
+    #[error("Invalid pool curve")]
+    InvalidCurve,
+    #[error("Insufficient pool shares")]
+    InsufficientShares,
+    #[error("Position does not exist")]
+    PositionNotFound,
+    #[error("Only the position owner can do this")]
+    NotPositionOwner,
+    #[error("Output below minimum")]
+    SlippageExceeded,
+    #[error("Initial liquidity is below the minimum")]
+    InsufficientInitialLiquidity,
+    #[error("Token is not in the pool")]
+    TokenNotInPool,
+    #[error("Invalid reward program")]
+    InvalidRewardProgram,
+    #[error("Reward program does not exist")]
+    RewardProgramNotFound,
+    #[error("Only the owner can do this")]
+    NotOwner,
+    #[error("Only an admin can do this")]
+    NotAdmin,
+    #[error("Trading is paused")]
+    Paused,
+    #[error("Caller is not a trusted minter for this token")]
+    NotTrustedMinter,
+    #[error("No authenticated signer or calling application")]
+    Unauthenticated,
+    #[error(transparent)]
+    Range(#[from] RangeError),
+    #[error(transparent)]
+    Weighted(#[from] WeightedError),
+}
+
+/// Event stream on which the DEX publishes `DexEvent`s
+pub const DEX_STREAM_NAME: &[u8] = b"dex_events";
+
+/// Events emitted on `DEX_STREAM_NAME`. These are serialized by variant index,
+/// so new variants must only ever be appended and existing ones never changed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DexEvent {
+    Swap {
+        trader: AccountOwner,
+        token_in: BridgeToken,
+        token_out: BridgeToken,
+        amount_in: Amount,
+        amount_out: Amount,
+    },
+    /// Liquidity added to a pool
+    Mint {
+        provider: AccountOwner,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        amount_a: Amount,
+        amount_b: Amount,
+        shares: Amount,
+    },
+    /// Liquidity removed from a pool
+    Burn {
+        provider: AccountOwner,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        amount_a: Amount,
+        amount_b: Amount,
+        shares: Amount,
+    },
+    PoolCreated {
+        creator: AccountOwner,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        fee_rate: u32,
+    },
+    FeeCollected {
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        token: BridgeToken,
+        amount: Amount,
+    },
+    /// Bridged tokens credited to a user
+    Deposit {
+        user: AccountOwner,
+        token: BridgeToken,
+        amount: Amount,
+    },
+    /// Bridged tokens burned for withdrawal
+    Withdrawal {
+        user: AccountOwner,
+        token: BridgeToken,
+        amount: Amount,
+    },
+    FlashSwap {
+        receiver: AccountOwner,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        amount_a_out: Amount,
+        amount_b_out: Amount,
+        amount_a_in: Amount,
+        amount_b_in: Amount,
+    },
+    WeightedPoolCreated {
+        creator: AccountOwner,
+        pool_id: u64,
+        tokens: Vec<BridgeToken>,
+        weights: Vec<u32>,
+        fee_rate: u32,
+    },
+    /// Liquidity added to a weighted pool; amounts follow the pool's token order
+    WeightedJoin {
+        provider: AccountOwner,
+        pool_id: u64,
+        amounts: Vec<Amount>,
+        shares: Amount,
+    },
+    /// Liquidity removed from a weighted pool; amounts follow the pool's token order
+    WeightedExit {
+        provider: AccountOwner,
+        pool_id: u64,
+        amounts: Vec<Amount>,
+        shares: Amount,
+    },
+    RewardProgramFunded {
+        funder: AccountOwner,
+        program_id: u64,
+        reward_token: BridgeToken,
+        amount: Amount,
+        end_time: u64,
+    },
+    RewardsClaimed {
+        user: AccountOwner,
+        program_id: u64,
+        reward_token: BridgeToken,
+        amount: Amount,
+    },
+    AdminAction {
+        actor: AccountOwner,
+        action: AdminActionKind,
+        detail: String,
+    },
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
 pub struct Pool {
     pub token_a: BridgeToken,
@@ -94,18 +607,362 @@ pub struct Pool {
     pub reserve_b: Amount,
     pub total_shares: Amount,
     pub fee_rate: u32,
+    pub curve: Curve,
+}
+
+impl Pool {
+    /// Output and fee for swapping `amount` of `from_token` in the pool, without changing it
+    pub fn quote_swap(&self, from_token: &BridgeToken, amount: Amount) -> Result<(u128, u128), DexError> {
+        // Calculate output on the pool's curve using the input after fees
+        let (input_reserve, output_reserve) = if *from_token == self.token_a {
+            (self.reserve_a, self.reserve_b)
+        } else {
+            (self.reserve_b, self.reserve_a)
+        };
+
+        let output_u128 = output_reserve.to_attos();
+        let amount_u128 = amount.to_attos();
+        let fee_u128 = math::swap_fee(amount_u128, self.fee_rate);
+
+        let output_amount_u128 = self
+            .curve
+            .output(input_reserve.to_attos(), output_u128, amount_u128 - fee_u128)
+            .ok_or(DexError::InvalidCalculation)?;
+
+        if output_amount_u128 >= output_u128 {
+            return Err(DexError::InsufficientPoolReserves);
+        }
+        Ok((output_amount_u128, fee_u128))
+    }
+
+    /// Moves a swap through the reserves; the fee stays in the pool for liquidity providers
+    pub fn apply_swap(&mut self, from_token: &BridgeToken, amount_in: u128, amount_out: u128) {
+        if *from_token == self.token_a {
+            self.reserve_a = Amount::from_attos(self.reserve_a.to_attos() + amount_in);
+            self.reserve_b = Amount::from_attos(self.reserve_b.to_attos() - amount_out);
+        } else {
+            self.reserve_b = Amount::from_attos(self.reserve_b.to_attos() + amount_in);
+            self.reserve_a = Amount::from_attos(self.reserve_a.to_attos() - amount_out);
+        }
+    }
+
+    /// Shares minted for a deposit and the amounts actually taken. Constant product
+    /// pools take the deposit in the current reserve ratio; stable pools take it all
+    /// and charge a fee on the imbalance.
+    pub fn quote_deposit(&self, amount_a: u128, amount_b: u128) -> Result<(u128, u128, u128), DexError> {
+        let total = self.total_shares.to_attos();
+        let (reserve_a, reserve_b) = (self.reserve_a.to_attos(), self.reserve_b.to_attos());
+        if total == 0 {
+            return Err(DexError::NoPoolShares);
+        }
+        if reserve_a == 0 || reserve_b == 0 {
+            return Err(DexError::ZeroReserve);
+        }
+
+        let (shares, used_a, used_b) = match self.curve {
+            Curve::ConstantProduct => {
+                let shares_a = math::mul_div(amount_a, total, reserve_a).ok_or(DexError::InvalidCalculation)?;
+                let shares_b = math::mul_div(amount_b, total, reserve_b).ok_or(DexError::InvalidCalculation)?;
+                if shares_a <= shares_b {
+                    let used_b = math::mul_div(amount_a, reserve_b, reserve_a).ok_or(DexError::InvalidCalculation)?;
+                    (shares_a, amount_a, (used_b + 1).min(amount_b))
+                } else {
+                    let used_a = math::mul_div(amount_b, reserve_a, reserve_b).ok_or(DexError::InvalidCalculation)?;
+                    (shares_b, (used_a + 1).min(amount_a), amount_b)
+                }
+            }
+            Curve::StableSwap { .. } => {
+                let new_a = reserve_a.checked_add(amount_a).ok_or(DexError::InvalidCalculation)?;
+                let new_b = reserve_b.checked_add(amount_b).ok_or(DexError::InvalidCalculation)?;
+                let d0 = self.curve.invariant(reserve_a, reserve_b).ok_or(DexError::InvalidCalculation)?;
+                let d1 = self.curve.invariant(new_a, new_b).ok_or(DexError::InvalidCalculation)?;
+                let adjusted = curve::stable_deposit_after_fees(
+                    (reserve_a, reserve_b),
+                    (new_a, new_b),
+                    d0,
+                    d1,
+                    self.fee_rate,
+                )
+                .ok_or(DexError::InvalidCalculation)?;
+                let d2 = self.curve.invariant(adjusted.0, adjusted.1).ok_or(DexError::InvalidCalculation)?;
+                let shares = math::mul_div(total, d2.saturating_sub(d0), d0).ok_or(DexError::InvalidCalculation)?;
+                (shares, amount_a, amount_b)
+            }
+        };
+        if shares == 0 {
+            return Err(DexError::InvalidCalculation);
+        }
+        Ok((shares, used_a, used_b))
+    }
+
+    pub fn apply_deposit(&mut self, shares: u128, amount_a: u128, amount_b: u128) {
+        self.reserve_a = Amount::from_attos(self.reserve_a.to_attos() + amount_a);
+        self.reserve_b = Amount::from_attos(self.reserve_b.to_attos() + amount_b);
+        self.total_shares = Amount::from_attos(self.total_shares.to_attos() + shares);
+    }
+
+    /// Part of a single-token deposit to swap so that the rest and the swap output
+    /// match the pool's reserve ratio after the swap
+    pub fn zap_swap_amount(&self, token: &BridgeToken, amount: u128) -> u128 {
+        let (reserve_in, reserve_out) = if *token == self.token_a {
+            (self.reserve_a.to_attos(), self.reserve_b.to_attos())
+        } else {
+            (self.reserve_b.to_attos(), self.reserve_a.to_attos())
+        };
+        // Swap more while the unswapped input is worth at least the output at the new
+        // ratio: (amount - x) / (reserve_in + x) >= out / (reserve_out - out)
+        let (mut low, mut high) = (0u128, amount);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            match self.quote_swap(token, Amount::from_attos(mid)) {
+                Ok((out, _)) if math::product_at_least(amount - mid, reserve_out - out, out, reserve_in + mid) => low = mid,
+                _ => high = mid - 1,
+            }
+        }
+        low
+    }
+
+    /// Quotes a deposit of `amount` of one pool token: the part swapped for the other
+    /// token, the swap output, and the shares and amounts of the deposit that follows
+    pub fn quote_single_deposit(&self, token: &BridgeToken, amount: u128) -> Result<(u128, u128, u128, u128, u128), DexError> {
+        let swap_in = self.zap_swap_amount(token, amount);
+        let (swap_out, _) = self.quote_swap(token, Amount::from_attos(swap_in))?;
+        let mut swapped = self.clone();
+        swapped.apply_swap(token, swap_in, swap_out);
+        let kept = amount - swap_in;
+        let (deposit_a, deposit_b) = if *token == self.token_a { (kept, swap_out) } else { (swap_out, kept) };
+        let (shares, used_a, used_b) = swapped.quote_deposit(deposit_a, deposit_b)?;
+        Ok((swap_in, swap_out, shares, used_a, used_b))
+    }
+
+    /// Proportional share of each reserve paid out for burning `shares`
+    pub fn quote_withdrawal(&self, shares: u128) -> Result<(u128, u128), DexError> {
+        let total = self.total_shares.to_attos();
+        if total == 0 {
+            return Err(DexError::NoPoolShares);
+        }
+        let out_a = math::mul_div(self.reserve_a.to_attos(), shares, total).ok_or(DexError::InvalidCalculation)?;
+        let out_b = math::mul_div(self.reserve_b.to_attos(), shares, total).ok_or(DexError::InvalidCalculation)?;
+        Ok((out_a, out_b))
+    }
+
+    pub fn apply_withdrawal(&mut self, shares: u128, out_a: u128, out_b: u128) {
+        self.reserve_a = Amount::from_attos(self.reserve_a.to_attos() - out_a);
+        self.reserve_b = Amount::from_attos(self.reserve_b.to_attos() - out_b);
+        self.total_shares = Amount::from_attos(self.total_shares.to_attos() - shares);
+    }
+
+    /// Quotes burning `shares` for `token` alone: the amount of it withdrawn, the amount
+    /// of the other token swapped back into the pool, and that swap's output
+    pub fn quote_single_withdrawal(&self, shares: u128, token: &BridgeToken) -> Result<(u128, u128, u128), DexError> {
+        let (out_a, out_b) = self.quote_withdrawal(shares)?;
+        let (kept, to_swap, other) = if *token == self.token_a {
+            (out_a, out_b, &self.token_b)
+        } else {
+            (out_b, out_a, &self.token_a)
+        };
+        if to_swap == 0 {
+            return Ok((kept, 0, 0));
+        }
+        let mut withdrawn = self.clone();
+        withdrawn.apply_withdrawal(shares, out_a, out_b);
+        let (swap_out, _) = withdrawn.quote_swap(other, Amount::from_attos(to_swap))?;
+        Ok((kept, to_swap, swap_out))
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct DexState {
     pub pools: HashMap<(BridgeToken, BridgeToken), Pool>,
-    pub user_balances: HashMap<(String, BridgeToken), Amount>, // (user, token) -> balance
-    pub bridge_tracker_app: Option<ApplicationId>, // Reference to bridge tracker
+    pub user_balances: HashMap<(AccountOwner, BridgeToken), Amount>, // (user, token) -> balance
+    pub bridge_tracker_app: Option<ApplicationId>, // Default minter for networks without a trusted minter
+    pub trusted_minters: HashMap<String, ApplicationId>, // network -> minting app
+    pub tokens: HashMap<String, TradingToken>, // symbol -> user-created token
+    pub observations: HashMap<(BridgeToken, BridgeToken), Vec<PriceObservation>>, // pool -> price history
+    pub trades: HashMap<(BridgeToken, BridgeToken), Vec<Trade>>, // pool -> trade log
+    pub volumes: HashMap<(BridgeToken, BridgeToken), PoolVolume>, // pool -> rolling volume and fees
+    pub orders: HashMap<u64, LimitOrder>, // order id -> limit order
+    pub next_order_id: u64,
+    pub lp_shares: HashMap<(AccountOwner, BridgeToken, BridgeToken), Amount>, // (user, token_a, token_b) -> pool shares
+    pub concentrated_pools: HashMap<(BridgeToken, BridgeToken), ConcentratedPool>,
+    pub positions: HashMap<u64, Position>, // position id -> concentrated liquidity position
+    pub next_position_id: u64,
+    pub weighted_pools: HashMap<u64, WeightedPool>,
+    pub next_weighted_pool_id: u64,
+    pub weighted_shares: HashMap<(AccountOwner, u64), Amount>, // (user, pool id) -> weighted pool shares
+    pub reward_programs: HashMap<u64, RewardProgram>,
+    pub next_reward_program_id: u64,
+    pub reward_stakes: HashMap<(u64, AccountOwner), RewardStake>, // (program id, user) -> settled rewards
+    pub owner: Option<AccountOwner>, // None once ownership is renounced
+    pub admins: HashSet<AccountOwner>,
+    pub admin_log: Vec<AdminAction>,
+    pub paused: bool,
+}
+
+/// The account acting on the DEX: the calling application when invoked through
+/// `call_application`, so apps trade from their own balances, otherwise the signer
+pub fn acting_account(caller: Option<ApplicationId>, signer: Option<AccountOwner>) -> Result<AccountOwner, DexError> {
+    match caller {
+        Some(app) => Ok(AccountOwner::from(app)),
+        None => signer.ok_or(DexError::Unauthenticated),
+    }
+}
+
+impl DexState {
+    pub fn balance(&self, user: &AccountOwner, token: &BridgeToken) -> Amount {
+        self.user_balances
+            .get(&(*user, token.clone()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_balance(&mut self, user: &AccountOwner, token: &BridgeToken, amount: Amount) {
+        self.user_balances.insert((*user, token.clone()), amount);
+    }
+
+    /// Returns the key of the pool trading `token_x` against `token_y`, in either order
+    pub fn find_pool_key(&self, token_x: &BridgeToken, token_y: &BridgeToken) -> Option<(BridgeToken, BridgeToken)> {
+        [(token_x.clone(), token_y.clone()), (token_y.clone(), token_x.clone())]
+            .into_iter()
+            .find(|pool_key| self.pools.contains_key(pool_key))
+    }
+
+    /// The app allowed to mint and burn `token`: its network's trusted minter, falling
+    /// back to the bridge tracker. Native tokens are never bridged.
+    pub fn minter_for(&self, token: &BridgeToken) -> Option<ApplicationId> {
+        if token.is_native() {
+            return None;
+        }
+        self.trusted_minters.get(&token.network).copied().or(self.bridge_tracker_app)
+    }
+
+    pub fn require_minter(&self, caller: Option<ApplicationId>, token: &BridgeToken) -> Result<(), DexError> {
+        match self.minter_for(token) {
+            Some(minter) if caller == Some(minter) => Ok(()),
+            _ => Err(DexError::NotTrustedMinter),
+        }
+    }
+
+    /// Validates a new pool without touching state and returns the shares it would mint
+    pub fn check_new_pool(
+        &self,
+        token_a: &BridgeToken,
+        token_b: &BridgeToken,
+        amount_a: Amount,
+        amount_b: Amount,
+        fee_rate: u32,
+        curve: &Curve,
+    ) -> Result<u128, DexError> {
+        if token_a == token_b {
+            return Err(DexError::IdenticalTokens);
+        }
+        if self.find_pool_key(token_a, token_b).is_some() {
+            return Err(DexError::PoolAlreadyExists);
+        }
+        if !math::is_valid_fee_rate(fee_rate) {
+            return Err(DexError::InvalidCalculation);
+        }
+        if !curve.is_valid() {
+            return Err(DexError::InvalidCurve);
+        }
+        if amount_a.is_zero() || amount_b.is_zero() {
+            return Err(DexError::ZeroReserve);
+        }
+        // Stable pools account shares in units of the invariant D, others by the geometric mean
+        let shares = match curve.invariant(amount_a.to_attos(), amount_b.to_attos()) {
+            Some(d) => d,
+            None if *curve == Curve::ConstantProduct => math::sqrt_product(amount_a.to_attos(), amount_b.to_attos()),
+            None => return Err(DexError::InvalidCalculation),
+        };
+        // The first MINIMUM_LIQUIDITY shares are locked forever, so the pool can never be
+        // drained back to zero shares and re-priced by a donation
+        if shares <= math::MINIMUM_LIQUIDITY {
+            return Err(DexError::InsufficientInitialLiquidity);
+        }
+        Ok(shares)
+    }
+
+    /// Records a new native token and credits its initial supply to the creator
+    pub fn register_token(
+        &mut self,
+        creator: &AccountOwner,
+        symbol: String,
+        name: String,
+        decimals: u8,
+        initial_supply: Amount,
+        mintable: bool,
+    ) -> Result<BridgeToken, DexError> {
+        if symbol.is_empty() || name.is_empty() || decimals > 18 {
+            return Err(DexError::InvalidTokenMetadata);
+        }
+        if self.tokens.contains_key(&symbol) {
+            return Err(DexError::TokenAlreadyExists);
+        }
+
+        let token = BridgeToken::native(&symbol);
+        self.tokens.insert(symbol.clone(), TradingToken {
+            symbol,
+            name,
+            decimals,
+            total_supply: initial_supply,
+            creator: *creator,
+            mintable,
+        });
+        self.set_balance(creator, &token, initial_supply);
+        Ok(token)
+    }
+
+    /// Mints more of a mintable native token on behalf of its creator
+    pub fn mint_token(&mut self, user: &AccountOwner, symbol: &str, recipient: &AccountOwner, amount: Amount) -> Result<(), DexError> {
+        let token = self.tokens.get_mut(symbol).ok_or(DexError::TokenNotFound)?;
+        if token.creator != *user {
+            return Err(DexError::NotTokenCreator);
+        }
+        if !token.mintable {
+            return Err(DexError::TokenNotMintable);
+        }
+        let total_supply = token
+            .total_supply
+            .to_attos()
+            .checked_add(amount.to_attos())
+            .ok_or(DexError::InvalidCalculation)?;
+        token.total_supply = Amount::from_attos(total_supply);
+
+        let token = BridgeToken::native(symbol);
+        let balance = self.balance(recipient, &token);
+        self.set_balance(recipient, &token, Amount::from_attos(balance.to_attos() + amount.to_attos()));
+        Ok(())
+    }
+
+    /// Validates a token launch's pool before anything is registered, so a launch
+    /// that cannot open its pool leaves no token behind
+    #[allow(clippy::too_many_arguments)]
+    pub fn check_launch(
+        &self,
+        user: &AccountOwner,
+        symbol: &str,
+        initial_supply: Amount,
+        quote_token: &BridgeToken,
+        token_amount: Amount,
+        quote_amount: Amount,
+        fee_rate: u32,
+    ) -> Result<(), DexError> {
+        if token_amount > initial_supply || self.balance(user, quote_token) < quote_amount {
+            return Err(DexError::InsufficientBalance);
+        }
+        let token = BridgeToken::native(symbol);
+        self.check_new_pool(&token, quote_token, token_amount, quote_amount, fee_rate, &Curve::ConstantProduct)?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, SimpleObject)]
 pub struct DexInstantiationArgument {
     pub bridge_tracker_app: Option<ApplicationId>,
+    #[serde(default)]
+    pub owner: Option<AccountOwner>, // Defaults to the account creating the application
+    #[serde(default)]
+    pub admins: Vec<AccountOwner>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,161 @@
+/// Computes `a * b / c` using a 256-bit intermediate product, rounding down.
+/// Returns `None` on division by zero or if the result does not fit in a `u128`.
+pub fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    if c == 0 {
+        return None;
+    }
+    let (hi, lo) = full_mul(a, b);
+    if hi == 0 {
+        return Some(lo / c);
+    }
+    if hi >= c {
+        return None;
+    }
+
+    // Long division of the 256-bit product; `hi < c` guarantees a 128-bit quotient
+    let mut remainder = hi;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((lo >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+/// Returns whether `a * b >= c * d`, comparing the full 256-bit products
+pub fn product_at_least(a: u128, b: u128, c: u128, d: u128) -> bool {
+    full_mul(a, b) >= full_mul(c, d)
+}
+
+/// Integer square root of `a * b`, rounded down, without overflowing the product
+pub fn sqrt_product(a: u128, b: u128) -> u128 {
+    // The root never exceeds the larger factor
+    let (mut low, mut high) = (0u128, a.max(b));
+    while low < high {
+        let mid = low + (high - low).div_ceil(2);
+        if product_at_least(a, b, mid, mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// Full 128x128 -> 256-bit multiplication, returned as `(high, low)` words
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+    let low_low = a_lo * b_lo;
+    let low_high = a_lo * b_hi;
+    let high_low = a_hi * b_lo;
+    let high_high = a_hi * b_hi;
+
+    let middle = (low_low >> 64) + (low_high & MASK) + (high_low & MASK);
+    let lo = (low_low & MASK) | (middle << 64);
+    let hi = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (hi, lo)
+}
+
+/// Shares burned from every new pool so its share price can never be inflated from zero
+pub const MINIMUM_LIQUIDITY: u128 = 1_000;
+
+/// Denominator for pool fee rates, which are expressed in basis points
+pub const FEE_DENOMINATOR: u128 = 10_000;
+
+/// Whether a pool may charge `fee_rate` basis points; a fee of 100% or more would
+/// consume the whole swap input
+pub fn is_valid_fee_rate(fee_rate: u32) -> bool {
+    u128::from(fee_rate) < FEE_DENOMINATOR
+}
+
+/// Fee taken from `amount_in` at `fee_rate` basis points
+pub fn swap_fee(amount_in: u128, fee_rate: u32) -> u128 {
+    mul_div(amount_in, u128::from(fee_rate), FEE_DENOMINATOR).unwrap_or(amount_in)
+}
+
+/// Constant-product output: dy = (y * dx) / (x + dx)
+pub fn cpmm_output(input_reserve: u128, output_reserve: u128, amount_in: u128) -> Option<u128> {
+    mul_div(output_reserve, amount_in, input_reserve.checked_add(amount_in)?)
+}
+
+/// Fixed-point one for the logarithm and exponential helpers below
+pub const ONE: u128 = 1_000_000_000_000_000_000;
+const LN_2: i128 = 693_147_180_559_945_309;
+/// Relative error bound on `pow`, used to round results in the pool's favour
+const MAX_POW_RELATIVE_ERROR: u128 = 10_000; // 1e-14
+
+/// Natural logarithm of a fixed-point `x`
+pub fn ln(x: u128) -> Option<i128> {
+    if x == 0 {
+        return None;
+    }
+    // Scale into [1, 2) by powers of two, then ln(m) = 2 * atanh((m - 1) / (m + 1))
+    let (mut mantissa, mut exponent) = (x, 0i128);
+    while mantissa >= 2 * ONE {
+        mantissa >>= 1;
+        exponent += 1;
+    }
+    while mantissa < ONE {
+        mantissa <<= 1;
+        exponent -= 1;
+    }
+    let z = (mantissa - ONE) * ONE / (mantissa + ONE);
+    let z_squared = z * z / ONE;
+    let (mut sum, mut term) = (z, z);
+    for n in (3..).step_by(2) {
+        term = term * z_squared / ONE;
+        if term == 0 {
+            break;
+        }
+        sum += term / n;
+    }
+    Some(exponent * LN_2 + 2 * sum as i128)
+}
+
+/// Exponential of a signed fixed-point `y`
+pub fn exp(y: i128) -> Option<u128> {
+    // Split y = k * ln(2) + r with r in [0, ln(2)) so the series converges quickly
+    let k = y.div_euclid(LN_2);
+    let r = y.rem_euclid(LN_2) as u128;
+    let (mut sum, mut term) = (ONE, ONE);
+    for n in 1.. {
+        term = term * r / ONE / n;
+        if term == 0 {
+            break;
+        }
+        sum += term;
+    }
+    if k >= 0 {
+        sum.checked_mul(1u128.checked_shl(u32::try_from(k).ok()?)?)
+    } else {
+        Some(sum.checked_shr(u32::try_from(-k).ok()?).unwrap_or(0))
+    }
+}
+
+/// `base ^ exponent` for fixed-point operands, exact to about 1e-16 relative
+fn pow(base: u128, exponent: u128) -> Option<u128> {
+    let log = ln(base)?;
+    let scaled = mul_div(log.unsigned_abs(), exponent, ONE)?;
+    let scaled = i128::try_from(scaled).ok()?;
+    exp(if log < 0 { -scaled } else { scaled })
+}
+
+/// `pow` rounded up past its error bound
+pub fn pow_up(base: u128, exponent: u128) -> Option<u128> {
+    let raw = pow(base, exponent)?;
+    raw.checked_add(mul_div(raw, MAX_POW_RELATIVE_ERROR, ONE)? + 1)
+}
+
+/// `pow` rounded down past its error bound
+pub fn pow_down(base: u128, exponent: u128) -> Option<u128> {
+    let raw = pow(base, exponent)?;
+    Some(raw.saturating_sub(mul_div(raw, MAX_POW_RELATIVE_ERROR, ONE)? + 1))
+}
@@ -0,0 +1,109 @@
+use async_graphql::SimpleObject;
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::{curve::Curve, BridgeToken, Pool};
+
+/// Fixed-point scale for prices, matching the attos precision of `Amount`
+pub const PRICE_SCALE: u128 = 1_000_000_000_000_000_000;
+/// Number of observations retained per pool
+pub const MAX_OBSERVATIONS: usize = 1024;
+
+/// Cumulative price accumulators at a point in time. Accumulators wrap on
+/// overflow; only differences between two observations are meaningful.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct PriceObservation {
+    pub timestamp: u64, // Block time in microseconds
+    pub price_a_cumulative: u128,
+    pub price_b_cumulative: u128,
+}
+
+// Time-weighted average price over a window
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct Twap {
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub price_a: Amount, // Price of token_a in token_b
+    pub price_b: Amount, // Price of token_b in token_a
+    pub window_micros: u64, // Span actually covered, may be shorter than requested
+}
+
+impl PriceObservation {
+    /// Extends the accumulators to `now` using the reserves in effect since this observation
+    pub fn advance(&self, now: u64, curve: &Curve, reserve_a: u128, reserve_b: u128) -> Self {
+        let elapsed = u128::from(now.saturating_sub(self.timestamp));
+        PriceObservation {
+            timestamp: now.max(self.timestamp),
+            price_a_cumulative: self
+                .price_a_cumulative
+                .wrapping_add(curve.spot_price(reserve_a, reserve_b).wrapping_mul(elapsed)),
+            price_b_cumulative: self
+                .price_b_cumulative
+                .wrapping_add(curve.spot_price(reserve_b, reserve_a).wrapping_mul(elapsed)),
+        }
+    }
+}
+
+/// Accumulates prices up to `now` with the pre-change reserves and appends the result.
+/// Must be called before the reserves are modified.
+pub fn record(
+    observations: &mut Vec<PriceObservation>,
+    now: u64,
+    curve: &Curve,
+    reserve_a: u128,
+    reserve_b: u128,
+) {
+    let next = match observations.last() {
+        Some(last) => last.advance(now, curve, reserve_a, reserve_b),
+        None => PriceObservation {
+            timestamp: now,
+            ..PriceObservation::default()
+        },
+    };
+    match observations.last_mut() {
+        Some(last) if last.timestamp == next.timestamp => *last = next,
+        _ => observations.push(next),
+    }
+    if observations.len() > MAX_OBSERVATIONS {
+        let excess = observations.len() - MAX_OBSERVATIONS;
+        observations.drain(..excess);
+    }
+}
+
+/// Average prices between `current` and the newest observation at least `window` old.
+/// Falls back to the oldest observation when history is shorter than the window.
+pub fn average_prices(
+    observations: &[PriceObservation],
+    current: &PriceObservation,
+    window: u64,
+) -> Option<(u128, u128, u64)> {
+    let target = current.timestamp.saturating_sub(window);
+    let start = observations
+        .iter()
+        .rev()
+        .find(|observation| observation.timestamp <= target)
+        .or_else(|| observations.first())?;
+    let elapsed = current.timestamp.checked_sub(start.timestamp).filter(|elapsed| *elapsed > 0)?;
+
+    let price_a = current.price_a_cumulative.wrapping_sub(start.price_a_cumulative) / u128::from(elapsed);
+    let price_b = current.price_b_cumulative.wrapping_sub(start.price_b_cumulative) / u128::from(elapsed);
+    Some((price_a, price_b, elapsed))
+}
+
+/// Builds the TWAP for a pool as of `now`, extrapolating from its latest observation
+pub fn twap(pool: &Pool, observations: &[PriceObservation], now: u64, window: u64) -> Option<Twap> {
+    let current = observations.last()?.advance(
+        now,
+        &pool.curve,
+        pool.reserve_a.to_attos(),
+        pool.reserve_b.to_attos(),
+    );
+    let (price_a, price_b, elapsed) = average_prices(observations, &current, window)?;
+    Some(Twap {
+        token_a: pool.token_a.clone(),
+        token_b: pool.token_b.clone(),
+        price_a: Amount::from_attos(price_a),
+        price_b: Amount::from_attos(price_b),
+        window_micros: elapsed,
+    })
+}
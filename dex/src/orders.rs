@@ -0,0 +1,144 @@
+use async_graphql::{Enum, SimpleObject};
+use linera_sdk::linera_base_types::{AccountOwner, Amount};
+use serde::{Deserialize, Serialize};
+
+use crate::{math::mul_div, oracle::PRICE_SCALE, BridgeToken, DexError, DexState, Pool};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Enum)]
+pub enum OrderSide {
+    Buy,  // Pay token_b for token_a
+    Sell, // Pay token_a for token_b
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Enum)]
+pub enum OrderStatus {
+    Open,
+    Filled,
+    Cancelled,
+    Expired,
+}
+
+// A resting order against a CPMM pool, with its input escrowed
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct LimitOrder {
+    pub id: u64,
+    pub owner: AccountOwner,
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub side: OrderSide,
+    pub amount_in: Amount,
+    pub min_price: Amount, // Minimum output received per unit of input
+    pub expiry: Option<u64>, // Block time in microseconds
+    pub status: OrderStatus,
+    pub amount_out: Amount,
+    pub created_at: u64,
+    pub closed_at: Option<u64>,
+}
+
+impl LimitOrder {
+    /// Tokens paid and received when the order fills
+    pub fn tokens(&self) -> (BridgeToken, BridgeToken) {
+        match self.side {
+            OrderSide::Buy => (self.token_b.clone(), self.token_a.clone()),
+            OrderSide::Sell => (self.token_a.clone(), self.token_b.clone()),
+        }
+    }
+
+    /// Smallest acceptable output for a full fill
+    pub fn min_output(&self) -> u128 {
+        mul_div(self.amount_in.to_attos(), self.min_price.to_attos(), PRICE_SCALE).unwrap_or(u128::MAX)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiry.is_some_and(|expiry| expiry <= now)
+    }
+
+    /// Whether swapping the whole input in `pool` now would meet the limit price
+    pub fn is_fillable(&self, pool: &Pool) -> bool {
+        let (input_token, _) = self.tokens();
+        pool.quote_swap(&input_token, self.amount_in)
+            .is_ok_and(|(output, _)| output >= self.min_output())
+    }
+}
+
+impl DexState {
+    /// Opens an order on the pool trading `token_a` against `token_b`, in either order,
+    /// and escrows its input. Matching it against the pool is left to the caller.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_order(
+        &mut self,
+        owner: &AccountOwner,
+        token_a: &BridgeToken,
+        token_b: &BridgeToken,
+        side: OrderSide,
+        amount_in: Amount,
+        min_price: Amount,
+        expiry: Option<u64>,
+        now: u64,
+    ) -> Result<u64, DexError> {
+        let (pool_a, pool_b) = self.find_pool_key(token_a, token_b).ok_or(DexError::PoolNotFound)?;
+        if amount_in.is_zero() || expiry.is_some_and(|expiry| expiry <= now) {
+            return Err(DexError::InvalidOrder);
+        }
+        // Orders are stored in the pool's token order, so a reversed pair flips the side
+        let side = match (pool_a == *token_a, side) {
+            (true, side) => side,
+            (false, OrderSide::Buy) => OrderSide::Sell,
+            (false, OrderSide::Sell) => OrderSide::Buy,
+        };
+
+        let id = self.next_order_id;
+        let order = LimitOrder {
+            id,
+            owner: *owner,
+            token_a: pool_a,
+            token_b: pool_b,
+            side,
+            amount_in,
+            min_price,
+            expiry,
+            status: OrderStatus::Open,
+            amount_out: Amount::ZERO,
+            created_at: now,
+            closed_at: None,
+        };
+
+        // Escrow the input until the order fills or is cancelled
+        let (input_token, _) = order.tokens();
+        let balance = self.balance(owner, &input_token);
+        if balance < amount_in {
+            return Err(DexError::InsufficientBalance);
+        }
+        self.set_balance(owner, &input_token, Amount::from_attos(balance.to_attos() - amount_in.to_attos()));
+
+        self.next_order_id += 1;
+        self.orders.insert(id, order);
+        Ok(id)
+    }
+
+    pub fn cancel_order(&mut self, owner: &AccountOwner, order_id: u64, now: u64) -> Result<(), DexError> {
+        let order = self.orders.get(&order_id).ok_or(DexError::OrderNotFound)?;
+        if order.owner != *owner {
+            return Err(DexError::NotOrderOwner);
+        }
+        if order.status != OrderStatus::Open {
+            return Err(DexError::OrderNotOpen);
+        }
+        self.close_order(order_id, OrderStatus::Cancelled, now);
+        Ok(())
+    }
+
+    /// Closes an open order without filling it and refunds the escrowed input
+    pub fn close_order(&mut self, order_id: u64, status: OrderStatus, now: u64) {
+        let Some(order) = self.orders.get_mut(&order_id) else {
+            return;
+        };
+        order.status = status;
+        order.closed_at = Some(now);
+        let (owner, amount_in) = (order.owner, order.amount_in);
+        let (input_token, _) = order.tokens();
+
+        let balance = self.balance(&owner, &input_token);
+        self.set_balance(&owner, &input_token, Amount::from_attos(balance.to_attos() + amount_in.to_attos()));
+    }
+}
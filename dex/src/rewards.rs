@@ -0,0 +1,110 @@
+use async_graphql::SimpleObject;
+use linera_sdk::linera_base_types::{AccountOwner, Amount};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{mul_div, MINIMUM_LIQUIDITY, ONE},
+    BridgeToken,
+};
+
+const MICROS_PER_SECOND: u128 = 1_000_000;
+
+// A funded emission of a reward token to a pool's liquidity providers
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct RewardProgram {
+    pub id: u64,
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub reward_token: BridgeToken,
+    pub funder: AccountOwner,
+    pub reward_rate: Amount, // Reward tokens emitted per second
+    pub start_time: u64,
+    pub end_time: u64,
+    pub last_update: u64,
+    pub distributed: Amount, // Emitted so far, claimed or not
+    #[graphql(skip)]
+    pub reward_per_share: u128, // Accumulated rewards per pool share, scaled by 1e18
+}
+
+/// A holder's position in a reward program, as of their last share change or claim
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct RewardStake {
+    pub reward_per_share_paid: u128,
+    pub pending: Amount,
+}
+
+impl RewardProgram {
+    pub fn new(
+        id: u64,
+        (token_a, token_b): (BridgeToken, BridgeToken),
+        reward_token: BridgeToken,
+        funder: AccountOwner,
+        now: u64,
+    ) -> Self {
+        RewardProgram {
+            id,
+            token_a,
+            token_b,
+            reward_token,
+            funder,
+            reward_rate: Amount::ZERO,
+            start_time: now,
+            end_time: now,
+            last_update: now,
+            distributed: Amount::ZERO,
+            reward_per_share: 0,
+        }
+    }
+
+    pub fn pool_key(&self) -> (BridgeToken, BridgeToken) {
+        (self.token_a.clone(), self.token_b.clone())
+    }
+
+    pub fn is_active(&self, now: u64) -> bool {
+        now < self.end_time
+    }
+
+    /// Emits rewards up to `now` across the pool's `total_shares`
+    pub fn accrue(&mut self, now: u64, total_shares: Amount) {
+        let until = now.min(self.end_time);
+        if until <= self.last_update {
+            return;
+        }
+        // The locked minimum liquidity has no holder to claim its part
+        let eligible_shares = total_shares.to_attos().saturating_sub(MINIMUM_LIQUIDITY);
+        if eligible_shares == 0 {
+            // Nobody can earn yet, so push the schedule back rather than lose the emission
+            self.end_time = self.end_time.saturating_add(now - self.last_update);
+            self.last_update = now;
+            return;
+        }
+        let elapsed = u128::from(until - self.last_update);
+        self.last_update = until;
+        let emitted = mul_div(self.reward_rate.to_attos(), elapsed, MICROS_PER_SECOND).unwrap_or(0);
+        let per_share = mul_div(emitted, ONE, eligible_shares).unwrap_or(0);
+        self.reward_per_share = self.reward_per_share.saturating_add(per_share);
+        self.distributed = self.distributed.saturating_add(Amount::from_attos(emitted));
+    }
+
+    /// Rewards owed to a holder of `shares` since their stake was last settled
+    pub fn earned(&self, shares: Amount, stake: &RewardStake) -> Amount {
+        let delta = self.reward_per_share - stake.reward_per_share_paid;
+        let owed = mul_div(shares.to_attos(), delta, ONE).unwrap_or(0);
+        stake.pending.saturating_add(Amount::from_attos(owed))
+    }
+
+    /// Rewards not yet emitted
+    pub fn remaining(&self, now: u64) -> Amount {
+        let left = u128::from(self.end_time.saturating_sub(now.max(self.last_update)));
+        Amount::from_attos(mul_div(self.reward_rate.to_attos(), left, MICROS_PER_SECOND).unwrap_or(0))
+    }
+
+    /// Spreads the unemitted balance plus `amount` evenly over the next `duration_secs`.
+    /// The program must already be accrued up to `now`.
+    pub fn refill(&mut self, now: u64, amount: Amount, duration_secs: u64) {
+        let total = self.remaining(now).saturating_add(amount);
+        self.reward_rate = Amount::from_attos(total.to_attos() / u128::from(duration_secs));
+        self.last_update = now;
+        self.end_time = now.saturating_add(duration_secs.saturating_mul(1_000_000));
+    }
+}
@@ -2,12 +2,23 @@
 
 use std::sync::Arc;
 
-use async_graphql::{EmptySubscription, Object, Schema, SimpleObject};
-use dex::{BridgeToken, DexAbi, DexOperation, DexState, Pool};
+use async_graphql::{EmptySubscription, Object, Schema, SimpleObject, Union};
+use dex::{
+    admin::AdminAction,
+    concentrated::{ConcentratedPool, Position},
+    history::{self, Candle, Trade, MAX_PAGE_SIZE},
+    math,
+    oracle::{self, Twap},
+    orders::{LimitOrder, OrderStatus},
+    rewards::RewardProgram,
+    stats::{self, PoolStats},
+    weighted::WeightedPool,
+    BridgeToken, DexAbi, DexOperation, DexState, Pool, TradingToken,
+};
 use linera_sdk::{
     abi::WithServiceAbi,
     graphql::GraphQLMutationRoot,
-    linera_base_types::Amount,
+    linera_base_types::{AccountOwner, Amount, ApplicationId},
     Service, ServiceRuntime,
 };
 
@@ -37,6 +48,7 @@ impl Service for DexService {
         Schema::build(
             QueryRoot {
                 state: self.state.clone(),
+                runtime: self.runtime.clone(),
             },
             DexOperation::mutation_root(self.runtime.clone()),
             EmptySubscription,
@@ -53,8 +65,68 @@ struct UserBalance {
     amount: Amount,
 }
 
+/// An app allowed to mint and burn bridged tokens, with the tokens it has minted so far
+#[derive(SimpleObject)]
+struct MintAuthorization {
+    network: Option<String>, // None for the bridge tracker's fallback over unassigned networks
+    minter: ApplicationId,
+    tokens: Vec<BridgeToken>,
+}
+
+/// Any pool listed by `allPools`: a two-token pool or a weighted pool
+#[derive(Union)]
+enum AnyPool {
+    Pair(Pool),
+    Weighted(WeightedPool),
+}
+
+#[derive(SimpleObject)]
+struct PendingReward {
+    program_id: u64,
+    reward_token: BridgeToken,
+    amount: Amount,
+}
+
 struct QueryRoot {
     state: Arc<DexState>,
+    runtime: Arc<ServiceRuntime<DexService>>,
+}
+
+impl QueryRoot {
+    /// A reward program brought up to the current time
+    fn accrued_program(&self, program: &RewardProgram) -> RewardProgram {
+        let mut program = program.clone();
+        if let Some(pool) = self.state.pools.get(&program.pool_key()) {
+            program.accrue(self.runtime.system_time().micros(), pool.total_shares);
+        }
+        program
+    }
+
+    fn pending_reward(&self, user: &AccountOwner, program: &RewardProgram) -> Amount {
+        let program = self.accrued_program(program);
+        let shares = self
+            .state
+            .lp_shares
+            .get(&(*user, program.token_a.clone(), program.token_b.clone()))
+            .copied()
+            .unwrap_or_default();
+        let stake = self
+            .state
+            .reward_stakes
+            .get(&(program.id, *user))
+            .copied()
+            .unwrap_or_default();
+        program.earned(shares, &stake)
+    }
+
+    fn with_accrued_fees(&self, position: &Position) -> Position {
+        let mut position = position.clone();
+        let pool_key = (position.token_a.clone(), position.token_b.clone());
+        if let Some(pool) = self.state.concentrated_pools.get(&pool_key) {
+            pool.accrue_fees(&mut position);
+        }
+        position
+    }
 }
 
 #[Object]
@@ -63,19 +135,341 @@ impl QueryRoot {
         self.state.pools.values().cloned().collect()
     }
 
+    /// Two-token pools followed by weighted pools; select fields with `... on Pool`
+    /// and `... on WeightedPool`
+    async fn all_pools(&self) -> Vec<AnyPool> {
+        let mut weighted_pools = self.state.weighted_pools.values().collect::<Vec<_>>();
+        weighted_pools.sort_by_key(|pool| pool.id);
+        self.state
+            .pools
+            .values()
+            .cloned()
+            .map(AnyPool::Pair)
+            .chain(weighted_pools.into_iter().cloned().map(AnyPool::Weighted))
+            .collect()
+    }
+
+    /// Weighted pools, optionally only those holding `token`
+    async fn weighted_pools(&self, token: Option<BridgeToken>) -> Vec<WeightedPool> {
+        let mut pools = self
+            .state
+            .weighted_pools
+            .values()
+            .filter(|pool| token.as_ref().is_none_or(|token| pool.index_of(token).is_ok()))
+            .cloned()
+            .collect::<Vec<_>>();
+        pools.sort_by_key(|pool| pool.id);
+        pools
+    }
+
+    async fn weighted_pool(&self, pool_id: u64) -> Option<WeightedPool> {
+        self.state.weighted_pools.get(&pool_id).cloned()
+    }
+
+    async fn user_weighted_shares(&self, user: AccountOwner, pool_id: u64) -> Amount {
+        self.state.weighted_shares
+            .get(&(user, pool_id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Output of a swap between two members of a weighted pool, if it would succeed
+    async fn estimate_weighted_swap(
+        &self,
+        pool_id: u64,
+        from_token: BridgeToken,
+        to_token: BridgeToken,
+        amount: Amount,
+    ) -> Option<Amount> {
+        let pool = self.state.weighted_pools.get(&pool_id)?;
+        let (amount_out, _) = pool.quote_swap(&from_token, &to_token, amount.to_attos()).ok()?;
+        Some(Amount::from_attos(amount_out))
+    }
+
     async fn pool_by_tokens(&self, token_a: BridgeToken, token_b: BridgeToken) -> Option<Pool> {
         let pool_key = (token_a, token_b);
         self.state.pools.get(&pool_key).cloned()
     }
 
-    async fn user_balance(&self, user: String, token: BridgeToken) -> Amount {
+    /// Time-weighted average price over the last `window_secs` seconds, with the tokens
+    /// in either order; prices are reported in the pool's token order
+    async fn twap(&self, token_a: BridgeToken, token_b: BridgeToken, window_secs: u64) -> Option<Twap> {
+        let pool_key = self.state.find_pool_key(&token_a, &token_b)?;
+        let pool = self.state.pools.get(&pool_key)?;
+        let observations = self.state.observations.get(&pool_key)?;
+        oracle::twap(
+            pool,
+            observations,
+            self.runtime.system_time().micros(),
+            window_secs.saturating_mul(1_000_000),
+        )
+    }
+
+    /// Swaps in the pool trading the two tokens, in either order, between `from` and `to`
+    /// (microseconds), oldest first
+    #[allow(clippy::too_many_arguments)]
+    async fn trades(
+        &self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        from: Option<u64>,
+        to: Option<u64>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<Trade> {
+        let Some(trades) = self
+            .state
+            .find_pool_key(&token_a, &token_b)
+            .and_then(|pool_key| self.state.trades.get(&pool_key))
+        else {
+            return vec![];
+        };
+        history::trades_in_range(trades, from.unwrap_or(0), to.unwrap_or(u64::MAX))
+            .iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(100).min(MAX_PAGE_SIZE))
+            .cloned()
+            .collect()
+    }
+
+    /// OHLCV candles of `interval_secs` seconds for the pool trading the two tokens, in
+    /// either order; prices are in the pool's token order
+    async fn candles(
+        &self,
+        token_a: BridgeToken,
+        token_b: BridgeToken,
+        interval_secs: u64,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Vec<Candle> {
+        let Some(trades) = self
+            .state
+            .find_pool_key(&token_a, &token_b)
+            .and_then(|pool_key| self.state.trades.get(&pool_key))
+        else {
+            return vec![];
+        };
+        let trades = history::trades_in_range(trades, from.unwrap_or(0), to.unwrap_or(u64::MAX));
+        history::candles(trades, interval_secs.saturating_mul(1_000_000))
+    }
+
+    /// TVL, rolling volume, fees and fee APR for the pool trading the two tokens, in either
+    /// order, valued in wUSDC
+    async fn pool_stats(&self, token_a: BridgeToken, token_b: BridgeToken) -> Option<PoolStats> {
+        let pool_key = self.state.find_pool_key(&token_a, &token_b)?;
+        let pool = self.state.pools.get(&pool_key)?;
+        let pools = self.state.pools.values().collect::<Vec<_>>();
+        let now = self.runtime.system_time().micros();
+        Some(stats::pool_stats(&pools, pool, self.state.volumes.get(&pool_key), now))
+    }
+
+    async fn all_pool_stats(&self) -> Vec<PoolStats> {
+        let pools = self.state.pools.values().collect::<Vec<_>>();
+        let now = self.runtime.system_time().micros();
+        self.state
+            .pools
+            .iter()
+            .map(|(pool_key, pool)| stats::pool_stats(&pools, pool, self.state.volumes.get(pool_key), now))
+            .collect()
+    }
+
+    /// A user's limit orders, optionally filtered by status, newest first
+    async fn user_orders(&self, user: AccountOwner, status: Option<OrderStatus>) -> Vec<LimitOrder> {
+        let mut orders = self
+            .state
+            .orders
+            .values()
+            .filter(|order| order.owner == user && status.is_none_or(|status| order.status == status))
+            .cloned()
+            .collect::<Vec<_>>();
+        orders.sort_by(|a, b| b.id.cmp(&a.id));
+        orders
+    }
+
+    /// Open orders resting on a pool, oldest first
+    async fn pool_orders(&self, token_a: BridgeToken, token_b: BridgeToken) -> Vec<LimitOrder> {
+        let mut orders = self
+            .state
+            .orders
+            .values()
+            .filter(|order| {
+                order.status == OrderStatus::Open && order.token_a == token_a && order.token_b == token_b
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|order| order.id);
+        orders
+    }
+
+    /// Pool shares held by a user
+    async fn user_shares(&self, user: AccountOwner, token_a: BridgeToken, token_b: BridgeToken) -> Amount {
+        self.state.lp_shares
+            .get(&(user, token_a, token_b))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Reward programs, optionally only those on one pool or still emitting
+    async fn reward_programs(
+        &self,
+        token_a: Option<BridgeToken>,
+        token_b: Option<BridgeToken>,
+        active_only: Option<bool>,
+    ) -> Vec<RewardProgram> {
+        let now = self.runtime.system_time().micros();
+        let mut programs = self
+            .state
+            .reward_programs
+            .values()
+            .filter(|program| token_a.as_ref().is_none_or(|token| program.token_a == *token))
+            .filter(|program| token_b.as_ref().is_none_or(|token| program.token_b == *token))
+            .filter(|program| !active_only.unwrap_or(false) || program.is_active(now))
+            .map(|program| self.accrued_program(program))
+            .collect::<Vec<_>>();
+        programs.sort_by_key(|program| program.id);
+        programs
+    }
+
+    async fn reward_program(&self, program_id: u64) -> Option<RewardProgram> {
+        let program = self.state.reward_programs.get(&program_id)?;
+        Some(self.accrued_program(program))
+    }
+
+    /// Unclaimed rewards for a user in every program they have earned from
+    async fn pending_rewards(&self, user: AccountOwner) -> Vec<PendingReward> {
+        let mut rewards = self
+            .state
+            .reward_programs
+            .values()
+            .map(|program| PendingReward {
+                program_id: program.id,
+                reward_token: program.reward_token.clone(),
+                amount: self.pending_reward(&user, program),
+            })
+            .filter(|reward| !reward.amount.is_zero())
+            .collect::<Vec<_>>();
+        rewards.sort_by_key(|reward| reward.program_id);
+        rewards
+    }
+
+    async fn concentrated_pools(&self) -> Vec<ConcentratedPool> {
+        self.state.concentrated_pools.values().cloned().collect()
+    }
+
+    async fn concentrated_pool(&self, token_a: BridgeToken, token_b: BridgeToken) -> Option<ConcentratedPool> {
+        self.state.concentrated_pools.get(&(token_a, token_b)).cloned()
+    }
+
+    /// A concentrated liquidity position with fees accrued up to now
+    async fn position(&self, position_id: u64) -> Option<Position> {
+        let position = self.state.positions.get(&position_id)?;
+        Some(self.with_accrued_fees(position))
+    }
+
+    async fn user_positions(&self, owner: AccountOwner) -> Vec<Position> {
+        self.state
+            .positions
+            .values()
+            .filter(|position| position.owner == owner && !position.liquidity.is_zero())
+            .map(|position| self.with_accrued_fees(position))
+            .collect()
+    }
+
+    /// Output of a concentrated liquidity swap, if it would succeed
+    async fn estimate_concentrated_swap(
+        &self,
+        from_token: BridgeToken,
+        to_token: BridgeToken,
+        amount: Amount,
+    ) -> Option<Amount> {
+        let (pool, a_to_b) = match self.state.concentrated_pools.get(&(from_token.clone(), to_token.clone())) {
+            Some(pool) => (pool, true),
+            None => (self.state.concentrated_pools.get(&(to_token, from_token))?, false),
+        };
+        let (_, outcome) = pool.quote_swap(a_to_b, amount.to_attos()).ok()?;
+        Some(Amount::from_attos(outcome.amount_out))
+    }
+
+    /// Which apps may mint which bridged tokens
+    async fn mint_authorizations(&self) -> Vec<MintAuthorization> {
+        let mut known_tokens = self
+            .state
+            .user_balances
+            .keys()
+            .map(|(_, token)| token)
+            .filter(|token| !token.is_native())
+            .cloned()
+            .collect::<Vec<_>>();
+        known_tokens.sort_by(|a, b| (&a.network, &a.symbol).cmp(&(&b.network, &b.symbol)));
+        known_tokens.dedup();
+
+        let mut authorizations = self
+            .state
+            .trusted_minters
+            .iter()
+            .map(|(network, minter)| MintAuthorization {
+                network: Some(network.clone()),
+                minter: *minter,
+                tokens: known_tokens.iter().filter(|token| token.network == *network).cloned().collect(),
+            })
+            .collect::<Vec<_>>();
+        authorizations.sort_by(|a, b| a.network.cmp(&b.network));
+        if let Some(minter) = self.state.bridge_tracker_app {
+            authorizations.push(MintAuthorization {
+                network: None,
+                minter,
+                tokens: known_tokens
+                    .iter()
+                    .filter(|token| !self.state.trusted_minters.contains_key(&token.network))
+                    .cloned()
+                    .collect(),
+            });
+        }
+        authorizations
+    }
+
+    async fn owner(&self) -> Option<AccountOwner> {
+        self.state.owner
+    }
+
+    async fn admins(&self) -> Vec<AccountOwner> {
+        let mut admins = self.state.admins.iter().copied().collect::<Vec<_>>();
+        admins.sort();
+        admins
+    }
+
+    async fn paused(&self) -> bool {
+        self.state.paused
+    }
+
+    /// Privileged actions taken on the DEX, newest first
+    async fn admin_log(&self, offset: Option<usize>, limit: Option<usize>) -> Vec<AdminAction> {
+        self.state
+            .admin_log
+            .iter()
+            .rev()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(100).min(MAX_PAGE_SIZE))
+            .cloned()
+            .collect()
+    }
+
+    async fn tokens(&self) -> Vec<TradingToken> {
+        self.state.tokens.values().cloned().collect()
+    }
+
+    async fn token(&self, symbol: String) -> Option<TradingToken> {
+        self.state.tokens.get(&symbol).cloned()
+    }
+
+    async fn user_balance(&self, user: AccountOwner, token: BridgeToken) -> Amount {
         self.state.user_balances
             .get(&(user, token))
             .copied()
             .unwrap_or_default()
     }
 
-    async fn user_balances(&self, user: String) -> Vec<UserBalance> {
+    async fn user_balances(&self, user: AccountOwner) -> Vec<UserBalance> {
         self.state.user_balances
             .iter()
             .filter(|((u, _), _)| u == &user)
@@ -87,8 +481,9 @@ impl QueryRoot {
     }
 
     async fn estimate_swap(&self, from_token: BridgeToken, to_token: BridgeToken, amount: Amount) -> Option<Amount> {
-        let pool_key = (from_token.clone(), to_token.clone());
-        let pool = self.state.pools.get(&pool_key)?;
+        let pool = self.state.pools
+            .get(&(from_token.clone(), to_token.clone()))
+            .or_else(|| self.state.pools.get(&(to_token, from_token.clone())))?;
 
         let (input_reserve, output_reserve) =
             if pool.token_a == from_token {
@@ -101,12 +496,11 @@ impl QueryRoot {
             return None;
         }
 
-        // Simple CPMM: output = (output_reserve * amount) / (input_reserve + amount)
-        let input_u128 = input_reserve.to_attos();
-        let output_u128 = output_reserve.to_attos();
+        // Pool curve on the input after fees
         let amount_u128 = amount.to_attos();
-        
-        let output_amount_u128 = (output_u128 * amount_u128) / (input_u128 + amount_u128);
-        Some(Amount::from_attos(output_amount_u128))
+        let amount_after_fee = amount_u128 - math::swap_fee(amount_u128, pool.fee_rate);
+        pool.curve
+            .output(input_reserve.to_attos(), output_reserve.to_attos(), amount_after_fee)
+            .map(Amount::from_attos)
     }
 }
@@ -0,0 +1,149 @@
+use async_graphql::SimpleObject;
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::mul_div,
+    oracle::PRICE_SCALE,
+    BridgeToken, Pool, BASE_TOKEN_SYMBOL,
+};
+
+/// Width of a volume bucket (one hour) in microseconds
+pub const BUCKET_MICROS: u64 = 3_600_000_000;
+pub const DAY_MICROS: u64 = 24 * BUCKET_MICROS;
+pub const WEEK_MICROS: u64 = 7 * DAY_MICROS;
+const DAYS_PER_YEAR: f64 = 365.0;
+
+// Traded amounts and fees within one bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VolumeBucket {
+    pub start: u64,
+    pub volume_a: Amount,
+    pub volume_b: Amount,
+    pub fees_a: Amount,
+    pub fees_b: Amount,
+}
+
+/// Hourly volume and fee counters for a pool, covering the last seven days
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PoolVolume {
+    pub buckets: Vec<VolumeBucket>,
+}
+
+// Analytics for a pool, valued in the base token (wUSDC)
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct PoolStats {
+    pub token_a: BridgeToken,
+    pub token_b: BridgeToken,
+    pub tvl: Option<Amount>,
+    pub volume_24h: Option<Amount>,
+    pub volume_7d: Option<Amount>,
+    pub fees_24h: Option<Amount>,
+    pub fees_7d: Option<Amount>,
+    pub fee_apr: Option<f64>, // Annualized from 24h fees, as a percentage
+}
+
+impl VolumeBucket {
+    fn add(&mut self, other: &VolumeBucket) {
+        self.volume_a = self.volume_a.saturating_add(other.volume_a);
+        self.volume_b = self.volume_b.saturating_add(other.volume_b);
+        self.fees_a = self.fees_a.saturating_add(other.fees_a);
+        self.fees_b = self.fees_b.saturating_add(other.fees_b);
+    }
+}
+
+impl PoolVolume {
+    /// Adds a swap to the bucket containing `now` and drops buckets older than a week
+    pub fn record(&mut self, now: u64, entry: VolumeBucket) {
+        let start = now - now % BUCKET_MICROS;
+        match self.buckets.last_mut() {
+            Some(bucket) if bucket.start == start => bucket.add(&entry),
+            _ => self.buckets.push(VolumeBucket { start, ..entry }),
+        }
+        let cutoff = now.saturating_sub(WEEK_MICROS);
+        self.buckets.retain(|bucket| bucket.start + BUCKET_MICROS > cutoff);
+    }
+
+    /// Sum of buckets overlapping the `span` microseconds before `now`
+    pub fn totals(&self, now: u64, span: u64) -> VolumeBucket {
+        let cutoff = now.saturating_sub(span);
+        let mut total = VolumeBucket::default();
+        for bucket in self.buckets.iter().filter(|bucket| bucket.start + BUCKET_MICROS > cutoff) {
+            total.add(bucket);
+        }
+        total
+    }
+}
+
+/// Price of `token` in the base token, read from a pool pairing it with the base
+pub fn base_price(pools: &[&Pool], token: &BridgeToken) -> Option<u128> {
+    if token.symbol == BASE_TOKEN_SYMBOL {
+        return Some(PRICE_SCALE);
+    }
+    pools.iter().find_map(|pool| {
+        if pool.token_a == *token && pool.token_b.symbol == BASE_TOKEN_SYMBOL {
+            Some(pool.curve.spot_price(pool.reserve_a.to_attos(), pool.reserve_b.to_attos()))
+        } else if pool.token_b == *token && pool.token_a.symbol == BASE_TOKEN_SYMBOL {
+            Some(pool.curve.spot_price(pool.reserve_b.to_attos(), pool.reserve_a.to_attos()))
+        } else {
+            None
+        }
+    })
+}
+
+fn value(amount: Amount, price: u128) -> u128 {
+    mul_div(amount.to_attos(), price, PRICE_SCALE).unwrap_or(u128::MAX)
+}
+
+/// Computes pool analytics. When only one side has a base price, the other is
+/// derived from this pool's own spot price.
+pub fn pool_stats(pools: &[&Pool], pool: &Pool, volume: Option<&PoolVolume>, now: u64) -> PoolStats {
+    let reserve_a = pool.reserve_a.to_attos();
+    let reserve_b = pool.reserve_b.to_attos();
+    let (price_a, price_b) = match (base_price(pools, &pool.token_a), base_price(pools, &pool.token_b)) {
+        (Some(price_a), Some(price_b)) => (Some(price_a), Some(price_b)),
+        (Some(price_a), None) => (
+            Some(price_a),
+            mul_div(pool.curve.spot_price(reserve_b, reserve_a), price_a, PRICE_SCALE),
+        ),
+        (None, Some(price_b)) => (
+            mul_div(pool.curve.spot_price(reserve_a, reserve_b), price_b, PRICE_SCALE),
+            Some(price_b),
+        ),
+        (None, None) => (None, None),
+    };
+
+    let priced = price_a.zip(price_b);
+    let day = volume.map(|volume| volume.totals(now, DAY_MICROS)).unwrap_or_default();
+    let week = volume.map(|volume| volume.totals(now, WEEK_MICROS)).unwrap_or_default();
+    let tvl = priced.map(|(price_a, price_b)| {
+        value(pool.reserve_a, price_a).saturating_add(value(pool.reserve_b, price_b))
+    });
+    // A swap moves equal value on both sides, so volume counts the token_a leg only
+    let volume_of = |bucket: &VolumeBucket| {
+        priced.map(|(price_a, _)| Amount::from_attos(value(bucket.volume_a, price_a)))
+    };
+    let fees_of = |bucket: &VolumeBucket| {
+        priced.map(|(price_a, price_b)| {
+            Amount::from_attos(value(bucket.fees_a, price_a).saturating_add(value(bucket.fees_b, price_b)))
+        })
+    };
+    let fees_24h = fees_of(&day);
+    let fee_apr = match (fees_24h, tvl) {
+        (Some(fees), Some(tvl)) if tvl > 0 => {
+            Some(fees.to_attos() as f64 / tvl as f64 * DAYS_PER_YEAR * 100.0)
+        }
+        _ => None,
+    };
+
+    PoolStats {
+        token_a: pool.token_a.clone(),
+        token_b: pool.token_b.clone(),
+        tvl: tvl.map(Amount::from_attos),
+        volume_24h: volume_of(&day),
+        volume_7d: volume_of(&week),
+        fees_24h,
+        fees_7d: fees_of(&week),
+        fee_apr,
+    }
+}
@@ -1,6 +1,27 @@
 #[cfg(test)]
 mod tests {
-    use linera_sdk::linera_base_types::Amount;
+    use linera_sdk::linera_base_types::{AccountOwner, Amount};
+
+    use crate::{curve::Curve, BridgeToken, DexState, Pool};
+
+    /// State holding a 1,000 ETH / 1,000 USD constant product pool charging 0.3%
+    fn eth_usd_pool() -> (DexState, BridgeToken, BridgeToken) {
+        let (eth, usd) = (BridgeToken::native("ETH"), BridgeToken::native("USD"));
+        let reserve = Amount::from_tokens(1_000);
+        let mut state = DexState::default();
+        let shares = state.check_new_pool(&eth, &usd, reserve, reserve, 30, &Curve::ConstantProduct).unwrap();
+        let pool = Pool {
+            token_a: eth.clone(),
+            token_b: usd.clone(),
+            reserve_a: reserve,
+            reserve_b: reserve,
+            total_shares: Amount::from_attos(shares),
+            fee_rate: 30,
+            curve: Curve::ConstantProduct,
+        };
+        state.pools.insert((eth.clone(), usd.clone()), pool);
+        (state, eth, usd)
+    }
 
     #[test]
     fn test_cpmm_formula() {
@@ -57,4 +78,711 @@ mod tests {
         let underflow = b.saturating_sub(a);
         assert_eq!(underflow, Amount::ZERO);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_mul_div_wide_product() {
+        use crate::math::mul_div;
+
+        // Product overflows u128 but the quotient fits
+        let a = 1u128 << 100;
+        assert_eq!(mul_div(a, a, 1u128 << 90), Some(1u128 << 110));
+        assert_eq!(mul_div(a, a, 1u128 << 60), None);
+        assert_eq!(mul_div(10, 20, 0), None);
+    }
+
+    #[test]
+    fn test_twap_accumulation() {
+        use crate::oracle::{average_prices, record, PRICE_SCALE};
+
+        let curve = Curve::ConstantProduct;
+        let mut observations = Vec::new();
+        // Price of A is 2.0 for 10s, then 4.0 for 10s
+        record(&mut observations, 0, &curve, 1000, 2000);
+        record(&mut observations, 10_000_000, &curve, 1000, 2000);
+        let current = observations
+            .last()
+            .unwrap()
+            .advance(20_000_000, &curve, 1000, 4000);
+
+        let (price_a, _, elapsed) = average_prices(&observations, &current, 20_000_000).unwrap();
+        assert_eq!(elapsed, 20_000_000);
+        assert_eq!(price_a, 3 * PRICE_SCALE);
+
+        let (price_a, price_b, _) = average_prices(&observations, &current, 10_000_000).unwrap();
+        assert_eq!(price_a, 4 * PRICE_SCALE);
+        assert_eq!(price_b, PRICE_SCALE / 4);
+    }
+
+    #[test]
+    fn test_candle_aggregation() {
+        use crate::history::{candles, execution_price, trades_in_range, SwapDirection, Trade};
+
+        let trade = |timestamp: u64, direction: SwapDirection, amount_in: u128, amount_out: u128| {
+            let (amount_in, amount_out) = (Amount::from_tokens(amount_in), Amount::from_tokens(amount_out));
+            Trade {
+                timestamp,
+                token_a: BridgeToken::native("AAA"),
+                token_b: BridgeToken::native("BBB"),
+                direction,
+                amount_in,
+                amount_out,
+                price: execution_price(direction, amount_in, amount_out),
+                trader: AccountOwner::Address20([1; 20]),
+            }
+        };
+        let trades = vec![
+            trade(1, SwapDirection::AToB, 10, 20),  // price 2
+            trade(5, SwapDirection::BToA, 30, 10),  // price 3
+            trade(12, SwapDirection::AToB, 10, 10), // price 1
+        ];
+
+        let buckets = candles(&trades, 10);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].open, Amount::from_tokens(2));
+        assert_eq!(buckets[0].high, Amount::from_tokens(3));
+        assert_eq!(buckets[0].close, Amount::from_tokens(3));
+        assert_eq!(buckets[0].volume_a, Amount::from_tokens(20));
+        assert_eq!(buckets[0].volume_b, Amount::from_tokens(50));
+        assert_eq!(buckets[1].start, 10);
+        assert_eq!(buckets[1].trades, 1);
+
+        assert_eq!(trades_in_range(&trades, 2, 12).len(), 1);
+        assert_eq!(trades_in_range(&trades, 20, 10).len(), 0);
+    }
+
+    #[test]
+    fn test_rolling_volume_window() {
+        use crate::stats::{PoolVolume, VolumeBucket, BUCKET_MICROS, DAY_MICROS, WEEK_MICROS};
+
+        let entry = |volume: u128| VolumeBucket {
+            volume_a: Amount::from_tokens(volume),
+            ..VolumeBucket::default()
+        };
+        let mut volume = PoolVolume::default();
+        volume.record(0, entry(5));
+        volume.record(2 * DAY_MICROS, entry(7));
+        volume.record(2 * DAY_MICROS + 10, entry(1));
+        assert_eq!(volume.buckets.len(), 2);
+
+        let now = 2 * DAY_MICROS + BUCKET_MICROS;
+        assert_eq!(volume.totals(now, DAY_MICROS).volume_a, Amount::from_tokens(8));
+        assert_eq!(volume.totals(now, WEEK_MICROS).volume_a, Amount::from_tokens(13));
+
+        // Buckets older than a week are dropped
+        volume.record(8 * DAY_MICROS, entry(2));
+        assert_eq!(volume.totals(8 * DAY_MICROS, WEEK_MICROS).volume_a, Amount::from_tokens(10));
+    }
+
+    #[test]
+    fn test_flash_invariant() {
+
+        let curve = Curve::ConstantProduct;
+        let old = (1_000_000u128, 1_000_000u128);
+        let out = (0u128, 10_000u128);
+        // Repaying the exact CPMM input leaves nothing for the 0.3% fee
+        let exact_in = 1_000_000 * 10_000 / (1_000_000 - 10_000) + 1;
+        assert!(curve.flash_repaid(old, out, (old.0 + exact_in, old.1 - out.1), 0));
+        assert!(!curve.flash_repaid(old, out, (old.0 + exact_in, old.1 - out.1), 30));
+        // Adding the fee on top restores it
+        let with_fee = exact_in * 10_000 / 9_970 + 1;
+        assert!(curve.flash_repaid(old, out, (old.0 + with_fee, old.1 - out.1), 30));
+        // Returning the borrowed tokens plus fee in kind also works
+        assert!(curve.flash_repaid(old, out, (old.0, old.1 + 31), 30));
+    }
+
+    #[test]
+    fn test_stableswap_curve() {
+        use crate::oracle::PRICE_SCALE;
+
+        let reserve = Amount::from_tokens(1_000_000).to_attos();
+        let amount = Amount::from_tokens(10_000).to_attos();
+        let stable = Curve::StableSwap { amplification: 100 };
+
+        // Near-1:1 output where constant product slips about 1%
+        let cp_out = Curve::ConstantProduct.output(reserve, reserve, amount).unwrap();
+        let stable_out = stable.output(reserve, reserve, amount).unwrap();
+        assert!(stable_out > cp_out);
+        assert!(stable_out > amount * 999 / 1000 && stable_out < amount);
+
+        // Balanced pools price at par and D equals the sum of reserves
+        assert_eq!(stable.spot_price(reserve, reserve), PRICE_SCALE);
+        assert_eq!(stable.invariant(reserve, reserve), Some(2 * reserve));
+        assert!(stable.spot_price(reserve, reserve / 2) < PRICE_SCALE);
+
+        assert!(!Curve::StableSwap { amplification: 0 }.is_valid());
+    }
+
+    #[test]
+    fn test_concentrated_fees_in_range() {
+        use crate::concentrated::{self, ConcentratedPool, Position};
+
+        for tick in [-400_000, -6931, 0, 1, 6931, 400_000] {
+            let sqrt_price = concentrated::sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(concentrated::tick_at_sqrt_price(sqrt_price), tick);
+        }
+
+        let sqrt_price = concentrated::sqrt_price_from_price(Amount::ONE).unwrap();
+        let same = BridgeToken::native("AAA");
+        assert!(ConcentratedPool::new(same.clone(), same, 30, 10, sqrt_price).is_err());
+        let mut pool = ConcentratedPool::new(BridgeToken::native("AAA"), BridgeToken::native("BBB"), 30, 10, sqrt_price).unwrap();
+        let position = |id, tick_lower, tick_upper| Position {
+            id,
+            owner: AccountOwner::Address20([2; 20]),
+            token_a: pool.token_a.clone(),
+            token_b: pool.token_b.clone(),
+            tick_lower,
+            tick_upper,
+            liquidity: Amount::ZERO,
+            tokens_owed_a: Amount::ZERO,
+            tokens_owed_b: Amount::ZERO,
+            fee_growth_inside_last_a: 0,
+            fee_growth_inside_last_b: 0,
+        };
+        let mut in_range = position(0, -1000, 1000);
+        let mut above = position(1, 2000, 3000);
+        let liquidity = Amount::from_tokens(1_000).to_attos() as i128;
+        pool.modify_position(&mut in_range, liquidity).unwrap();
+        pool.modify_position(&mut above, liquidity).unwrap();
+
+        let amount_in = Amount::from_tokens(10).to_attos();
+        let outcome = pool.swap(true, amount_in).unwrap();
+        assert_eq!(outcome.amount_in, amount_in);
+        assert_eq!(outcome.fee, amount_in * 30 / 10_000);
+        assert!(outcome.amount_out > 0 && outcome.amount_out < amount_in);
+
+        // Only the position covering the traded range earns the fee, less Q64.64 rounding dust
+        pool.accrue_fees(&mut in_range);
+        pool.accrue_fees(&mut above);
+        let earned = in_range.tokens_owed_a.to_attos();
+        assert!(earned <= outcome.fee && outcome.fee - earned < 1_000);
+        assert!(above.tokens_owed_a.is_zero());
+    }
+
+    #[test]
+    fn test_weighted_pool() {
+        use crate::math::ONE;
+        use crate::weighted::{WeightedError, WeightedPool};
+
+        let (eth, btc, usd) = (BridgeToken::native("ETH"), BridgeToken::native("BTC"), BridgeToken::native("USD"));
+        let tokens = vec![eth.clone(), btc.clone(), usd.clone()];
+        let amounts = vec![Amount::from_tokens(100), Amount::from_tokens(5), Amount::from_tokens(150_000)];
+        let pool = WeightedPool::new(0, tokens, vec![4_000, 4_000, 2_000], amounts, 30).unwrap();
+
+        // Spot price reflects balances and weights: (150000 / 0.2) / (100 / 0.4)
+        assert_eq!(pool.spot_price(&eth, &usd), Some(3_000 * ONE));
+        let (out, fee) = pool.quote_swap(&eth, &usd, ONE).unwrap();
+        assert_eq!(fee, ONE * 30 / 10_000);
+        assert!(out > 2_900 * ONE && out < 2_991 * ONE);
+        assert_eq!(pool.quote_swap(&btc, &eth, 2 * ONE), Err(WeightedError::RatioExceeded));
+
+        // A single-asset join followed by the matching exit only loses the implicit swap fees
+        let deposit = 3_000 * ONE;
+        let shares = pool.quote_join(&usd, deposit).unwrap();
+        let mut joined = pool.clone();
+        joined.adjust_balance(2, deposit, true);
+        joined.total_shares = joined.total_shares.saturating_add(Amount::from_attos(shares));
+        let back = joined.quote_exit(&usd, shares).unwrap();
+        assert!(back < deposit && back > deposit * 99 / 100);
+
+        // An equal-weight pair behaves like constant product
+        let pair = WeightedPool::new(1, vec![eth.clone(), usd.clone()], vec![5_000, 5_000], vec![Amount::from_tokens(1_000); 2], 0).unwrap();
+        let (out, _) = pair.quote_swap(&eth, &usd, 10 * ONE).unwrap();
+        let cpmm = 1_000 * ONE * 10 / 1_010;
+        assert!(out <= cpmm && cpmm - out < ONE / 1_000_000);
+    }
+
+    #[test]
+    fn test_sqrt_product() {
+        use crate::math::{sqrt_product, MINIMUM_LIQUIDITY};
+
+        assert_eq!(sqrt_product(0, 1_000), 0);
+        assert_eq!(sqrt_product(4, 9), 6);
+        assert_eq!(sqrt_product(10, 10), 10);
+        assert_eq!(sqrt_product(2, 3), 2);
+        // Product far beyond u128
+        let amount = Amount::from_tokens(1_000_000_000).to_attos();
+        assert_eq!(sqrt_product(amount, 4 * amount), 2 * amount);
+        assert_eq!(sqrt_product(u128::MAX, u128::MAX), u128::MAX);
+        // Dust deposits cannot clear the locked minimum
+        assert!(sqrt_product(999, 1_000) <= MINIMUM_LIQUIDITY);
+    }
+
+    #[test]
+    fn test_reward_accrual() {
+        use crate::math::MINIMUM_LIQUIDITY;
+        use crate::rewards::{RewardProgram, RewardStake};
+
+        let second = 1_000_000u64;
+        let pool_key = (BridgeToken::native("AAA"), BridgeToken::native("BBB"));
+        let mut program = RewardProgram::new(0, pool_key, BridgeToken::native("RWD"), AccountOwner::Address20([3; 20]), 0);
+        program.refill(0, Amount::from_tokens(100), 100);
+        assert_eq!(program.reward_rate, Amount::ONE);
+
+        // While only the locked shares exist nothing is emitted and the schedule moves back
+        let locked = Amount::from_attos(MINIMUM_LIQUIDITY);
+        program.accrue(5 * second, locked);
+        assert_eq!(program.distributed, Amount::ZERO);
+        assert_eq!(program.end_time, 105 * second);
+        assert_eq!(program.remaining(5 * second), Amount::from_tokens(100));
+
+        // Alice holds all 10 shares for 10 seconds, then Bob joins with 30 more
+        let alice = Amount::from_tokens(10);
+        let mut alice_stake = RewardStake::default();
+        program.accrue(15 * second, alice.saturating_add(locked));
+        alice_stake.pending = program.earned(alice, &alice_stake);
+        alice_stake.reward_per_share_paid = program.reward_per_share;
+        let bob = Amount::from_tokens(30);
+        let bob_stake = RewardStake { reward_per_share_paid: program.reward_per_share, pending: Amount::ZERO };
+
+        let total = alice.saturating_add(bob).saturating_add(locked);
+        program.accrue(35 * second, total);
+        assert_eq!(program.earned(alice, &alice_stake), Amount::from_tokens(15));
+        assert_eq!(program.earned(bob, &bob_stake), Amount::from_tokens(15));
+
+        // Emission stops at the end time, and refilling spreads the remainder anew
+        program.accrue(500 * second, total);
+        assert_eq!(program.distributed, Amount::from_tokens(100));
+        assert_eq!(program.remaining(500 * second), Amount::ZERO);
+        program.refill(500 * second, Amount::from_tokens(10), 10);
+        assert_eq!(program.remaining(500 * second), Amount::from_tokens(10));
+        program.accrue(505 * second, total);
+        assert_eq!(program.distributed, Amount::from_tokens(105));
+    }
+
+    #[test]
+    fn test_find_pool_key_either_order() {
+        let (state, eth, usd) = eth_usd_pool();
+        let pool_key = Some((eth.clone(), usd.clone()));
+        assert_eq!(state.find_pool_key(&eth, &usd), pool_key);
+        assert_eq!(state.find_pool_key(&usd, &eth), pool_key);
+        assert_eq!(state.find_pool_key(&eth, &BridgeToken::native("BTC")), None);
+    }
+
+    #[test]
+    fn test_create_pool_rejects_duplicates() {
+        use crate::DexError;
+
+        let (state, eth, usd) = eth_usd_pool();
+        let reserve = Amount::from_tokens(1_000);
+        let check = |token_a: &BridgeToken, token_b: &BridgeToken| {
+            state.check_new_pool(token_a, token_b, reserve, reserve, 30, &Curve::ConstantProduct)
+        };
+
+        // Neither the reversed pair nor a token against itself opens a pool
+        assert!(matches!(check(&usd, &eth), Err(DexError::PoolAlreadyExists)));
+        assert!(matches!(check(&eth, &eth), Err(DexError::IdenticalTokens)));
+        assert_eq!(check(&BridgeToken::native("BTC"), &usd).unwrap(), reserve.to_attos());
+    }
+
+    #[test]
+    fn test_swap_charges_pool_fee() {
+        use crate::math::cpmm_output;
+
+        let (mut state, eth, usd) = eth_usd_pool();
+        let pool = state.pools.get_mut(&(eth.clone(), usd)).unwrap();
+        let amount = Amount::from_tokens(10).to_attos();
+        let reserve = Amount::from_tokens(1_000).to_attos();
+
+        let (out, fee) = pool.quote_swap(&eth, Amount::from_attos(amount)).unwrap();
+        assert_eq!(fee, amount * 30 / 10_000);
+        assert_eq!(out, cpmm_output(reserve, reserve, amount - fee).unwrap());
+
+        // The fee stays in the pool's reserves
+        pool.apply_swap(&eth, amount, out);
+        assert_eq!(pool.reserve_a, Amount::from_attos(reserve + amount));
+        assert_eq!(pool.reserve_b, Amount::from_attos(reserve - out));
+    }
+
+    #[test]
+    fn test_fee_rate_validation() {
+        use crate::math::is_valid_fee_rate;
+        use crate::DexError;
+
+        assert!(is_valid_fee_rate(0) && is_valid_fee_rate(9_999));
+        assert!(!is_valid_fee_rate(10_000) && !is_valid_fee_rate(u32::MAX));
+
+        // Pools charging 100% or more are rejected before they can break every swap
+        let (state, _, usd) = eth_usd_pool();
+        let (btc, reserve) = (BridgeToken::native("BTC"), Amount::from_tokens(1_000));
+        let open = |fee_rate| state.check_new_pool(&btc, &usd, reserve, reserve, fee_rate, &Curve::ConstantProduct);
+        assert!(matches!(open(10_000), Err(DexError::InvalidCalculation)));
+        assert!(open(9_999).is_ok());
+
+        // At the highest valid fee almost all of the input stays in the pool
+        let mut pool = state.pools.values().next().unwrap().clone();
+        pool.fee_rate = 9_999;
+        let amount = Amount::from_tokens(10);
+        let (out, fee) = pool.quote_swap(&usd, amount).unwrap();
+        assert_eq!(fee, amount.to_attos() * 9_999 / 10_000);
+        assert!(out > 0 && out < amount.to_attos() - fee);
+    }
+
+    #[test]
+    fn test_limit_order_fills_when_price_crosses() {
+        use crate::oracle::PRICE_SCALE;
+        use crate::orders::{OrderSide, OrderStatus};
+
+        let (mut state, eth, usd) = eth_usd_pool();
+        let owner = AccountOwner::Address20([2; 20]);
+        state.set_balance(&owner, &eth, Amount::from_tokens(10));
+
+        // Paying ETH for USD at 1.05 or better, given as a buy on the reversed pair
+        let min_price = Amount::from_attos(PRICE_SCALE * 105 / 100);
+        let order_id = state
+            .open_order(&owner, &usd, &eth, OrderSide::Buy, Amount::from_tokens(10), min_price, None, 0)
+            .unwrap();
+        let order = state.orders[&order_id].clone();
+        assert_eq!((&order.token_a, &order.token_b, order.side), (&eth, &usd, OrderSide::Sell));
+        assert_eq!(order.status, OrderStatus::Open);
+        assert_eq!(state.balance(&owner, &eth), Amount::ZERO);
+
+        // Buying ETH out of the pool lifts its price past the limit
+        let pool = state.pools.get_mut(&(eth.clone(), usd.clone())).unwrap();
+        assert!(!order.is_fillable(pool));
+        let amount = Amount::from_tokens(100);
+        let (out, _) = pool.quote_swap(&usd, amount).unwrap();
+        pool.apply_swap(&usd, amount.to_attos(), out);
+        assert!(order.is_fillable(pool));
+        let (filled, _) = pool.quote_swap(&eth, order.amount_in).unwrap();
+        assert!(filled >= Amount::from_tokens(10).to_attos() * 105 / 100);
+    }
+
+    #[test]
+    fn test_limit_order_cancel_and_expiry() {
+        use crate::orders::{OrderSide, OrderStatus};
+        use crate::DexError;
+
+        let (mut state, eth, usd) = eth_usd_pool();
+        let owner = AccountOwner::Address20([2; 20]);
+        state.set_balance(&owner, &usd, Amount::from_tokens(20));
+        let place = |state: &mut DexState, amount_in: u128, expiry: Option<u64>, now: u64| {
+            state.open_order(&owner, &eth, &usd, OrderSide::Buy, Amount::from_tokens(amount_in), Amount::from_tokens(2), expiry, now)
+        };
+        let cancelled = place(&mut state, 10, None, 0).unwrap();
+        let expiring = place(&mut state, 10, Some(1_000), 0).unwrap();
+        assert_eq!(state.balance(&owner, &usd), Amount::ZERO);
+
+        // Empty, already expired and unfunded orders are refused
+        assert!(matches!(place(&mut state, 0, None, 0), Err(DexError::InvalidOrder)));
+        assert!(matches!(place(&mut state, 10, Some(1_000), 1_000), Err(DexError::InvalidOrder)));
+        assert!(matches!(place(&mut state, 10, None, 0), Err(DexError::InsufficientBalance)));
+
+        // Only the owner cancels, and the escrow comes back
+        let creator = AccountOwner::Address20([1; 20]);
+        assert!(matches!(state.cancel_order(&creator, cancelled, 500), Err(DexError::NotOrderOwner)));
+        state.cancel_order(&owner, cancelled, 500).unwrap();
+        assert_eq!(state.orders[&cancelled].status, OrderStatus::Cancelled);
+        assert_eq!(state.orders[&cancelled].closed_at, Some(500));
+        assert_eq!(state.balance(&owner, &usd), Amount::from_tokens(10));
+        assert!(matches!(state.cancel_order(&owner, cancelled, 500), Err(DexError::OrderNotOpen)));
+
+        // Past its expiry an order is closed out and refunded
+        assert!(!state.orders[&expiring].is_expired(999));
+        assert!(state.orders[&expiring].is_expired(1_000));
+        state.close_order(expiring, OrderStatus::Expired, 2_000);
+        assert_eq!(state.orders[&expiring].status, OrderStatus::Expired);
+        assert_eq!(state.balance(&owner, &usd), Amount::from_tokens(20));
+        assert!(matches!(state.cancel_order(&owner, expiring, 2_000), Err(DexError::OrderNotOpen)));
+    }
+
+    #[test]
+    fn test_zap_swap_amount() {
+        use crate::math::product_at_least;
+
+        let (state, eth, usd) = eth_usd_pool();
+        let pool = &state.pools[&(eth.clone(), usd)];
+        let (reserve, amount) = (Amount::from_tokens(1_000).to_attos(), Amount::from_tokens(100).to_attos());
+
+        // The largest swap that leaves the rest no more valuable than the output at the new ratio
+        let balanced = |swap_in: u128| {
+            let (out, _) = pool.quote_swap(&eth, Amount::from_attos(swap_in)).unwrap();
+            product_at_least(amount - swap_in, reserve - out, out, reserve + swap_in)
+        };
+        let swap_in = pool.zap_swap_amount(&eth, amount);
+        assert!(balanced(swap_in) && !balanced(swap_in + 1));
+        // A little under half, since swapping moves the price against the deposit
+        assert!(swap_in < amount / 2 && swap_in > amount * 45 / 100);
+        assert_eq!(pool.zap_swap_amount(&eth, 0), 0);
+    }
+
+    #[test]
+    fn test_single_sided_liquidity() {
+        let (state, eth, usd) = eth_usd_pool();
+        let mut pool = state.pools[&(eth.clone(), usd.clone())].clone();
+        let amount = Amount::from_tokens(100).to_attos();
+
+        // Almost everything goes into the pool, on both sides
+        let (swap_in, swap_out, shares, used_a, used_b) = pool.quote_single_deposit(&eth, amount).unwrap();
+        let dust = Amount::from_tokens(1).to_attos() / 1_000;
+        assert!(amount - swap_in - used_a < dust);
+        assert!(swap_out - used_b < dust);
+        pool.apply_swap(&eth, swap_in, swap_out);
+        pool.apply_deposit(shares, used_a, used_b);
+
+        // The round trip only costs the fees on the two swaps
+        let (kept, to_swap, swap_back) = pool.quote_single_withdrawal(shares, &eth).unwrap();
+        let received = kept + swap_back;
+        assert!(to_swap > 0);
+        assert!(received < amount && received >= Amount::from_tokens(99).to_attos());
+
+        // Withdrawing into the other token swaps the other side instead
+        let (kept_usd, to_swap_eth, _) = pool.quote_single_withdrawal(shares, &usd).unwrap();
+        assert_eq!((kept_usd, to_swap_eth), (to_swap, kept));
+    }
+
+    #[test]
+    fn test_create_and_mint_token() {
+        use crate::DexError;
+
+        let (creator, other) = (AccountOwner::Address20([1; 20]), AccountOwner::Address20([2; 20]));
+        let token = BridgeToken::native("FAST");
+        let mut state = DexState::default();
+        let create = |state: &mut DexState, decimals: u8| {
+            state.register_token(&creator, "FAST".to_string(), "Fast".to_string(), decimals, Amount::from_tokens(1_000), true)
+        };
+
+        assert!(matches!(create(&mut state, 19), Err(DexError::InvalidTokenMetadata)));
+        assert_eq!(create(&mut state, 18).unwrap(), token);
+        assert_eq!(state.balance(&creator, &token), Amount::from_tokens(1_000));
+        assert!(matches!(create(&mut state, 18), Err(DexError::TokenAlreadyExists)));
+
+        state.mint_token(&creator, "FAST", &other, Amount::from_tokens(50)).unwrap();
+        assert_eq!(state.balance(&other, &token), Amount::from_tokens(50));
+        assert_eq!(state.tokens["FAST"].total_supply, Amount::from_tokens(1_050));
+
+        // Only the creator mints, and only while the token is mintable
+        let fifty = Amount::from_tokens(50);
+        assert!(matches!(state.mint_token(&other, "FAST", &other, fifty), Err(DexError::NotTokenCreator)));
+        assert!(matches!(state.mint_token(&creator, "SLOW", &other, fifty), Err(DexError::TokenNotFound)));
+        state.tokens.get_mut("FAST").unwrap().mintable = false;
+        assert!(matches!(state.mint_token(&creator, "FAST", &other, fifty), Err(DexError::TokenNotMintable)));
+        assert_eq!(state.balance(&other, &token), fifty);
+    }
+
+    #[test]
+    fn test_launch_checks_pool_first() {
+        use crate::DexError;
+
+        let creator = AccountOwner::Address20([1; 20]);
+        let usd = BridgeToken::native("USD");
+        let mut state = DexState::default();
+        state.set_balance(&creator, &usd, Amount::from_tokens(100));
+        let launch = |state: &DexState, symbol: &str, token_amount: u128, quote_amount: u128| {
+            let (token_amount, quote_amount) = (Amount::from_tokens(token_amount), Amount::from_tokens(quote_amount));
+            state.check_launch(&creator, symbol, Amount::from_tokens(1_000), &usd, token_amount, quote_amount, 30)
+        };
+
+        // A launch whose pool cannot open fails before the token is registered
+        assert!(matches!(launch(&state, "FAST", 0, 100), Err(DexError::ZeroReserve)));
+        assert!(matches!(launch(&state, "USD", 400, 100), Err(DexError::IdenticalTokens)));
+        assert!(matches!(launch(&state, "FAST", 1_001, 100), Err(DexError::InsufficientBalance)));
+        assert!(matches!(launch(&state, "FAST", 400, 101), Err(DexError::InsufficientBalance)));
+        launch(&state, "FAST", 400, 100).unwrap();
+        assert!(state.tokens.is_empty());
+    }
+
+    #[test]
+    fn test_admin_roles() {
+        use crate::{admin::AdminActionKind, DexError};
+
+        let (owner, admin, other) = (
+            AccountOwner::Address20([1; 20]),
+            AccountOwner::Address20([2; 20]),
+            AccountOwner::Address20([3; 20]),
+        );
+        let (mut state, eth, usd) = eth_usd_pool();
+        state.owner = Some(owner);
+
+        // Only the owner manages admins
+        assert!(matches!(state.add_admin(other, admin, 1), Err(DexError::NotOwner)));
+        state.add_admin(owner, admin, 1).unwrap();
+        assert!(matches!(state.add_admin(admin, other, 2), Err(DexError::NotOwner)));
+
+        // Admins and the owner run admin operations; others cannot
+        state.set_pool_fee(admin, &usd, &eth, 50, 2).unwrap();
+        assert_eq!(state.pools[&(eth.clone(), usd.clone())].fee_rate, 50);
+        state.set_pool_fee(owner, &eth, &usd, 40, 3).unwrap();
+        assert!(matches!(state.set_pool_fee(other, &eth, &usd, 30, 3), Err(DexError::NotAdmin)));
+        assert_eq!(state.pools[&(eth.clone(), usd.clone())].fee_rate, 40);
+
+        state.remove_admin(owner, admin, 4).unwrap();
+        assert!(matches!(state.remove_admin(owner, admin, 4), Err(DexError::NotAdmin)));
+        assert!(matches!(state.set_paused(admin, true, 5), Err(DexError::NotAdmin)));
+
+        state.add_admin(owner, other, 6).unwrap();
+        state.renounce_admin(other, 7).unwrap();
+        assert!(matches!(state.renounce_admin(other, 7), Err(DexError::NotAdmin)));
+        assert!(state.admins.is_empty());
+
+        // Every accepted action is logged in order with its actor and time; refusals are not
+        let log = state
+            .admin_log
+            .iter()
+            .map(|entry| (entry.timestamp, entry.actor, entry.action))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            log,
+            [
+                (1, owner, AdminActionKind::AddAdmin),
+                (2, admin, AdminActionKind::SetPoolFee),
+                (3, owner, AdminActionKind::SetPoolFee),
+                (4, owner, AdminActionKind::RemoveAdmin),
+                (6, owner, AdminActionKind::AddAdmin),
+                (7, other, AdminActionKind::RenounceAdmin),
+            ]
+        );
+        assert_eq!(state.admin_log[1].detail, "ETH/USD: 30 -> 50 bps");
+    }
+
+    #[test]
+    fn test_ownership_transfer_and_renounce() {
+        use crate::{admin::AdminActionKind, DexError};
+
+        let (owner, admin, successor) = (
+            AccountOwner::Address20([1; 20]),
+            AccountOwner::Address20([2; 20]),
+            AccountOwner::Address20([3; 20]),
+        );
+        let mut state = DexState {
+            owner: Some(owner),
+            ..DexState::default()
+        };
+        state.add_admin(owner, admin, 1).unwrap();
+
+        assert!(matches!(state.transfer_ownership(admin, admin, 2), Err(DexError::NotOwner)));
+        state.transfer_ownership(owner, successor, 2).unwrap();
+        assert_eq!(state.owner, Some(successor));
+        assert!(matches!(state.add_admin(owner, owner, 3), Err(DexError::NotOwner)));
+        assert!(matches!(state.set_paused(owner, true, 3), Err(DexError::NotAdmin)));
+
+        // Renouncing leaves the DEX ownerless while admins keep their roles
+        assert!(matches!(state.renounce_ownership(owner, 4), Err(DexError::NotOwner)));
+        state.renounce_ownership(successor, 4).unwrap();
+        assert_eq!(state.owner, None);
+        assert!(matches!(state.add_admin(successor, successor, 5), Err(DexError::NotOwner)));
+        state.set_paused(admin, true, 5).unwrap();
+
+        let actions = state.admin_log.iter().map(|entry| entry.action).collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            [
+                AdminActionKind::AddAdmin,
+                AdminActionKind::TransferOwnership,
+                AdminActionKind::RenounceOwnership,
+                AdminActionKind::SetPaused,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pause_halts_trading_only() {
+        use crate::DexOperation;
+
+        let owner = AccountOwner::Address20([1; 20]);
+        let (mut state, eth, usd) = eth_usd_pool();
+        state.owner = Some(owner);
+        state.set_paused(owner, true, 1).unwrap();
+        assert!(state.paused);
+        assert_eq!(state.admin_log[0].detail, "true");
+
+        let swap = DexOperation::SwapTokens {
+            from_token: eth.clone(),
+            to_token: usd.clone(),
+            amount: Amount::ONE,
+        };
+        let remove = DexOperation::RemoveLiquidity {
+            token_a: eth.clone(),
+            token_b: usd.clone(),
+            share_amount: Amount::ONE,
+        };
+        let remove_single = DexOperation::RemoveLiquiditySingle {
+            token_a: eth,
+            token_b: usd.clone(),
+            share_amount: Amount::ONE,
+            token: usd,
+            min_amount: Amount::ZERO,
+        };
+        assert!(swap.is_halted_by_pause());
+        assert!(remove_single.is_halted_by_pause());
+        assert!(!remove.is_halted_by_pause());
+        assert!(!DexOperation::SetPaused { paused: false }.is_halted_by_pause());
+
+        state.set_paused(owner, false, 2).unwrap();
+        assert!(!state.paused);
+    }
+
+    #[test]
+    fn test_trusted_minters() {
+        use linera_sdk::linera_base_types::{ApplicationId, CryptoHash};
+
+        use crate::DexError;
+
+        let owner = AccountOwner::Address20([1; 20]);
+        let app = |n: u64| ApplicationId::new(CryptoHash::from([n; 4]));
+        let (tracker, solana_minter, stranger) = (app(1), app(2), app(3));
+        let bridged = |network: &str| BridgeToken {
+            symbol: "wUSDC".to_string(),
+            network: network.to_string(),
+        };
+        let (sepolia_usdc, solana_usdc) = (bridged("sepolia"), bridged("solana"));
+        let mut state = DexState {
+            owner: Some(owner),
+            ..DexState::default()
+        };
+
+        // Nothing mints until a minter is configured, and native tokens never
+        assert!(matches!(state.require_minter(Some(tracker), &sepolia_usdc), Err(DexError::NotTrustedMinter)));
+        state.set_bridge_tracker_app(owner, Some(tracker), 1).unwrap();
+        state.set_trusted_minter(owner, "solana".to_string(), Some(solana_minter), 2).unwrap();
+        assert!(matches!(
+            state.require_minter(Some(tracker), &BridgeToken::native("USD")),
+            Err(DexError::NotTrustedMinter)
+        ));
+
+        // A network's own minter mints only that network
+        state.require_minter(Some(solana_minter), &solana_usdc).unwrap();
+        assert!(matches!(state.require_minter(Some(solana_minter), &sepolia_usdc), Err(DexError::NotTrustedMinter)));
+
+        // The tracker covers the remaining networks but not one with its own minter
+        state.require_minter(Some(tracker), &sepolia_usdc).unwrap();
+        assert!(matches!(state.require_minter(Some(tracker), &solana_usdc), Err(DexError::NotTrustedMinter)));
+        assert!(matches!(state.require_minter(Some(stranger), &sepolia_usdc), Err(DexError::NotTrustedMinter)));
+        assert!(matches!(state.require_minter(None, &sepolia_usdc), Err(DexError::NotTrustedMinter)));
+
+        // Revoking the network minter hands the network back to the tracker
+        state.set_trusted_minter(owner, "solana".to_string(), None, 3).unwrap();
+        assert!(matches!(state.require_minter(Some(solana_minter), &solana_usdc), Err(DexError::NotTrustedMinter)));
+        state.require_minter(Some(tracker), &solana_usdc).unwrap();
+        state.set_bridge_tracker_app(owner, None, 4).unwrap();
+        assert!(matches!(state.require_minter(Some(tracker), &solana_usdc), Err(DexError::NotTrustedMinter)));
+
+        // Only admins configure minters, and never for the native network
+        let minter = Some(stranger);
+        assert!(matches!(
+            state.set_trusted_minter(AccountOwner::Address20([2; 20]), "solana".to_string(), minter, 5),
+            Err(DexError::NotAdmin)
+        ));
+        assert!(matches!(
+            state.set_trusted_minter(owner, "linera".to_string(), minter, 5),
+            Err(DexError::InvalidTokenMetadata)
+        ));
+        assert!(matches!(state.set_trusted_minter(owner, String::new(), minter, 5), Err(DexError::InvalidTokenMetadata)));
+        assert_eq!(state.admin_log.len(), 4);
+    }
+
+    #[test]
+    fn test_acting_account() {
+        use linera_sdk::linera_base_types::{ApplicationId, CryptoHash};
+
+        use crate::{acting_account, DexError};
+
+        let app = ApplicationId::new(CryptoHash::from([7; 4]));
+        let signer = AccountOwner::Address20([1; 20]);
+
+        // A calling application acts from its own account, even when the block has a signer
+        assert_eq!(acting_account(Some(app), None).unwrap(), AccountOwner::from(app));
+        assert_eq!(acting_account(Some(app), Some(signer)).unwrap(), AccountOwner::from(app));
+        assert_eq!(acting_account(None, Some(signer)).unwrap(), signer);
+        assert!(matches!(acting_account(None, None), Err(DexError::Unauthenticated)));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::math::{self, mul_div, FEE_DENOMINATOR, ONE};
+use crate::BridgeToken;
+use async_graphql::SimpleObject;
+use linera_sdk::linera_base_types::Amount;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Token weights are expressed in basis points and must sum to this
+pub const WEIGHT_DENOMINATOR: u32 = 10_000;
+pub const MIN_WEIGHT: u32 = 100; // 1%
+pub const MAX_WEIGHTED_TOKENS: usize = 8;
+/// Trades and single-asset joins or exits may move at most 30% of a token's balance,
+/// which keeps the power approximations well inside their accurate range
+const MAX_RATIO: u128 = 3_000;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightedError {
+    #[error("Weighted pools need 2 to 8 distinct tokens with weights summing to 100%")]
+    InvalidWeights,
+    #[error("Token is not in the pool")]
+    UnknownToken,
+    #[error("Amount exceeds 30% of the pool balance")]
+    RatioExceeded,
+    #[error("Invalid amount")]
+    InvalidAmount,
+    #[error("Calculation overflow")]
+    Overflow,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct WeightedAsset {
+    pub token: BridgeToken,
+    pub weight: u32, // Basis points of the pool's value
+    pub balance: Amount,
+}
+
+// A pool of two or more tokens holding the weighted product of balances constant
+#[derive(Debug, Clone, Deserialize, Serialize, SimpleObject)]
+pub struct WeightedPool {
+    pub id: u64,
+    pub assets: Vec<WeightedAsset>,
+    pub total_shares: Amount,
+    pub fee_rate: u32,
+}
+
+fn weight_fraction(weight: u32) -> u128 {
+    u128::from(weight) * ONE / u128::from(WEIGHT_DENOMINATOR)
+}
+
+fn within_ratio(amount: u128, balance: u128) -> Result<(), WeightedError> {
+    let limit = mul_div(balance, MAX_RATIO, FEE_DENOMINATOR).ok_or(WeightedError::Overflow)?;
+    if amount > limit {
+        return Err(WeightedError::RatioExceeded);
+    }
+    Ok(())
+}
+
+/// Part of a single-asset join or exit that is an implicit swap against the other tokens
+fn implicit_swap_fee(amount: u128, weight: u32, fee_rate: u32) -> Option<u128> {
+    let taxable = mul_div(amount, u128::from(WEIGHT_DENOMINATOR - weight), u128::from(WEIGHT_DENOMINATOR))?;
+    Some(math::swap_fee(taxable, fee_rate))
+}
+
+impl WeightedPool {
+    /// Builds a pool from its initial deposit, minting the weighted geometric mean of
+    /// the balances as shares
+    pub fn new(
+        id: u64,
+        tokens: Vec<BridgeToken>,
+        weights: Vec<u32>,
+        amounts: Vec<Amount>,
+        fee_rate: u32,
+    ) -> Result<Self, WeightedError> {
+        let count = tokens.len();
+        if !(2..=MAX_WEIGHTED_TOKENS).contains(&count)
+            || weights.len() != count
+            || weights.iter().any(|weight| *weight < MIN_WEIGHT)
+            || weights.iter().sum::<u32>() != WEIGHT_DENOMINATOR
+            || (1..count).any(|i| tokens[..i].contains(&tokens[i]))
+        {
+            return Err(WeightedError::InvalidWeights);
+        }
+        if amounts.len() != count || amounts.iter().any(|amount| amount.is_zero()) || !math::is_valid_fee_rate(fee_rate) {
+            return Err(WeightedError::InvalidAmount);
+        }
+
+        let mut log_invariant = 0i128;
+        for (amount, weight) in amounts.iter().zip(&weights) {
+            let log = math::ln(amount.to_attos()).ok_or(WeightedError::Overflow)?;
+            log_invariant += log * i128::from(*weight) / i128::from(WEIGHT_DENOMINATOR);
+        }
+        let invariant = math::exp(log_invariant).ok_or(WeightedError::Overflow)?;
+
+        let assets = tokens
+            .into_iter()
+            .zip(weights)
+            .zip(amounts)
+            .map(|((token, weight), balance)| WeightedAsset { token, weight, balance })
+            .collect();
+        Ok(WeightedPool {
+            id,
+            assets,
+            total_shares: Amount::from_attos(invariant),
+            fee_rate,
+        })
+    }
+
+    pub fn index_of(&self, token: &BridgeToken) -> Result<usize, WeightedError> {
+        self.assets
+            .iter()
+            .position(|asset| asset.token == *token)
+            .ok_or(WeightedError::UnknownToken)
+    }
+
+    pub fn tokens(&self) -> Vec<BridgeToken> {
+        self.assets.iter().map(|asset| asset.token.clone()).collect()
+    }
+
+    /// Spot price of `from` in units of `to`, scaled by 1e18
+    pub fn spot_price(&self, from: &BridgeToken, to: &BridgeToken) -> Option<u128> {
+        let from = &self.assets[self.index_of(from).ok()?];
+        let to = &self.assets[self.index_of(to).ok()?];
+        let numerator = mul_div(to.balance.to_attos(), u128::from(from.weight), u128::from(to.weight))?;
+        mul_div(numerator, ONE, from.balance.to_attos())
+    }
+
+    /// Output and fee for swapping `amount_in` of `from` into `to`:
+    /// out = B_out * (1 - (B_in / (B_in + in))^(w_in / w_out))
+    pub fn quote_swap(&self, from: &BridgeToken, to: &BridgeToken, amount_in: u128) -> Result<(u128, u128), WeightedError> {
+        let (i, o) = (self.index_of(from)?, self.index_of(to)?);
+        if i == o || amount_in == 0 {
+            return Err(WeightedError::InvalidAmount);
+        }
+        let (asset_in, asset_out) = (&self.assets[i], &self.assets[o]);
+        let (balance_in, balance_out) = (asset_in.balance.to_attos(), asset_out.balance.to_attos());
+        within_ratio(amount_in, balance_in)?;
+
+        let fee = math::swap_fee(amount_in, self.fee_rate);
+        let after_fee = amount_in - fee;
+        let denominator = balance_in.checked_add(after_fee).ok_or(WeightedError::Overflow)?;
+        // Round the base and power up so the output rounds down
+        let base = mul_div(balance_in, ONE, denominator).ok_or(WeightedError::Overflow)? + 1;
+        let exponent = mul_div(u128::from(asset_in.weight), ONE, u128::from(asset_out.weight)).ok_or(WeightedError::Overflow)?;
+        let power = math::pow_up(base.min(ONE), exponent).ok_or(WeightedError::Overflow)?;
+        let amount_out = mul_div(balance_out, ONE.saturating_sub(power), ONE).ok_or(WeightedError::Overflow)?;
+        within_ratio(amount_out, balance_out)?;
+        Ok((amount_out, fee))
+    }
+
+    /// Shares minted for a single-asset deposit, charging the swap fee on the part of
+    /// the deposit that rebalances into the other tokens
+    pub fn quote_join(&self, token: &BridgeToken, amount_in: u128) -> Result<u128, WeightedError> {
+        let asset = &self.assets[self.index_of(token)?];
+        let balance = asset.balance.to_attos();
+        if amount_in == 0 {
+            return Err(WeightedError::InvalidAmount);
+        }
+        within_ratio(amount_in, balance)?;
+
+        let fee = implicit_swap_fee(amount_in, asset.weight, self.fee_rate).ok_or(WeightedError::Overflow)?;
+        let balance_ratio = mul_div(balance + amount_in - fee, ONE, balance).ok_or(WeightedError::Overflow)?;
+        let invariant_ratio = math::pow_down(balance_ratio, weight_fraction(asset.weight)).ok_or(WeightedError::Overflow)?;
+        mul_div(self.total_shares.to_attos(), invariant_ratio.saturating_sub(ONE), ONE).ok_or(WeightedError::Overflow)
+    }
+
+    /// Tokens paid out for burning `shares` into a single asset
+    pub fn quote_exit(&self, token: &BridgeToken, shares: u128) -> Result<u128, WeightedError> {
+        let asset = &self.assets[self.index_of(token)?];
+        let total_shares = self.total_shares.to_attos();
+        if shares == 0 || shares >= total_shares {
+            return Err(WeightedError::InvalidAmount);
+        }
+
+        // Round the remaining invariant share up so the payout rounds down
+        let invariant_ratio = mul_div(total_shares - shares, ONE, total_shares).ok_or(WeightedError::Overflow)? + 1;
+        let exponent = mul_div(ONE, u128::from(WEIGHT_DENOMINATOR), u128::from(asset.weight)).ok_or(WeightedError::Overflow)?;
+        let balance_ratio = math::pow_up(invariant_ratio.min(ONE), exponent).ok_or(WeightedError::Overflow)?;
+        let balance = asset.balance.to_attos();
+        let gross = mul_div(balance, ONE.saturating_sub(balance_ratio), ONE).ok_or(WeightedError::Overflow)?;
+        let fee = implicit_swap_fee(gross, asset.weight, self.fee_rate).ok_or(WeightedError::Overflow)?;
+        let amount_out = gross - fee;
+        within_ratio(amount_out, balance)?;
+        Ok(amount_out)
+    }
+
+    /// Each token's share of a proportional exit
+    pub fn quote_proportional_exit(&self, shares: u128) -> Result<Vec<u128>, WeightedError> {
+        let total_shares = self.total_shares.to_attos();
+        if shares == 0 || shares > total_shares {
+            return Err(WeightedError::InvalidAmount);
+        }
+        self.assets
+            .iter()
+            .map(|asset| mul_div(asset.balance.to_attos(), shares, total_shares).ok_or(WeightedError::Overflow))
+            .collect()
+    }
+
+    /// Credits `amount` to a token's balance, or debits it when `deposit` is false
+    pub fn adjust_balance(&mut self, index: usize, amount: u128, deposit: bool) {
+        let asset = &mut self.assets[index];
+        let balance = asset.balance.to_attos();
+        asset.balance = Amount::from_attos(if deposit {
+            balance.saturating_add(amount)
+        } else {
+            balance.saturating_sub(amount)
+        });
+    }
+}
@@ -22,7 +22,9 @@ BRIDGE_TRACKER_ID=$(linera publish-and-create \
     "ethereum_endpoint": "http://localhost:8545",
     "bridge_contract": "0x7449478525Eb5106f487d44672B40592Af2a4E49",
     "usdc_contract": "0x1c7D4B196Cb0C7B01d743Fbc6116a902379C7238",
-    "start_block": 0
+    "start_block": 0,
+    "chain_id": 31337,
+    "network": "sepolia"
   }')
 
 echo "🏪 Deploying DEX..."
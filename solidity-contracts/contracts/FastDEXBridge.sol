@@ -8,39 +8,99 @@ contract Vault is ReentrancyGuard {
     uint256 public depositNonce = 0;
     mapping(uint256 => address) public userDeposits;
 
-    constructor(address _usdcToken) {
+    // Read by the Linera bridge tracker; `token` is address(0) for ether
+    event Deposit(address indexed user, address indexed token, uint256 amount, uint256 indexed nonce);
+
+    // Withdrawals are authorized by EIP-712 vouchers signed by the Linera bridge relayers
+    bytes32 public constant WITHDRAWAL_TYPEHASH =
+        keccak256("Withdrawal(address user,address token,uint256 amount,uint256 nonce,uint256 deadline)");
+    bytes32 public immutable DOMAIN_SEPARATOR;
+    mapping(address => bool) public isRelayer;
+    uint256 public immutable threshold;
+    mapping(uint256 => bool) public usedWithdrawals;
+
+    constructor(address _usdcToken, address[] memory _relayers, uint256 _threshold) {
+        require(_threshold > 0 && _threshold <= _relayers.length, "Invalid threshold");
         usdcToken = _usdcToken;
+        for (uint256 i = 0; i < _relayers.length; i++) {
+            isRelayer[_relayers[i]] = true;
+        }
+        threshold = _threshold;
+        DOMAIN_SEPARATOR = keccak256(
+            abi.encode(
+                keccak256("EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)"),
+                keccak256(bytes("FastDEX Vault")),
+                keccak256(bytes("1")),
+                block.chainid,
+                address(this)
+            )
+        );
     }
 
     function depositUSDC(uint256 amount, address user) external nonReentrant {
         require(user != address(0), "Invalid user address");
         userDeposits[depositNonce] = user;
         // Assuming USDC is a standard ERC20 token
-        IERC20(usdcToken).transferFrom(msg.sender, address(this), amount);
+        require(IERC20(usdcToken).transferFrom(msg.sender, address(this), amount), "Transfer failed");
+        emit Deposit(user, usdcToken, amount, depositNonce);
         depositNonce++;
     }
 
     function depositETH(address user) external payable nonReentrant {
         require(user != address(0), "Invalid user address");
+        require(msg.value > 0, "No ether sent");
         userDeposits[depositNonce] = user;
+        emit Deposit(user, address(0), msg.value, depositNonce);
         depositNonce++;
     }
 
-    function withdrawUSDC(uint256 nonce, uint256 amount, uint256 deadline) external nonReentrant {
-        require(block.timestamp <= deadline, "Deadline expired");
-        require(userDeposits[nonce] == msg.sender, "Not authorized");
-        IERC20(usdcToken).transfer(msg.sender, amount);
+    function withdrawUSDC(uint256 nonce, uint256 amount, uint256 deadline, bytes[] calldata signatures) external nonReentrant {
+        _useVoucher(usdcToken, nonce, amount, deadline, signatures);
+        require(IERC20(usdcToken).transfer(msg.sender, amount), "Transfer failed");
     }
 
-    function withdrawETH(uint256 nonce, uint256 amount, uint256 deadline) external nonReentrant {
-        require(block.timestamp <= deadline, "Deadline expired");
-        require(userDeposits[nonce] == msg.sender, "Not authorized");
+    function withdrawETH(uint256 nonce, uint256 amount, uint256 deadline, bytes[] calldata signatures) external nonReentrant {
+        _useVoucher(address(0), nonce, amount, deadline, signatures);
         (bool success, ) = msg.sender.call{value: amount}("");
         require(success, "ETH transfer failed");
     }
+
+    // Checks that enough relayers signed a voucher paying the caller, then spends it.
+    // Signatures must be ordered by ascending signer address so none counts twice.
+    function _useVoucher(address token, uint256 nonce, uint256 amount, uint256 deadline, bytes[] calldata signatures) internal {
+        require(block.timestamp <= deadline, "Deadline expired");
+        require(!usedWithdrawals[nonce], "Voucher already used");
+        require(signatures.length >= threshold, "Not enough signatures");
+        usedWithdrawals[nonce] = true;
+
+        bytes32 structHash = keccak256(abi.encode(WITHDRAWAL_TYPEHASH, msg.sender, token, amount, nonce, deadline));
+        bytes32 digest = keccak256(abi.encodePacked("\x19\x01", DOMAIN_SEPARATOR, structHash));
+        address previous = address(0);
+        for (uint256 i = 0; i < signatures.length; i++) {
+            address signer = _recover(digest, signatures[i]);
+            require(signer > previous, "Signatures not in signer order");
+            require(isRelayer[signer], "Not a relayer");
+            previous = signer;
+        }
+    }
+
+    function _recover(bytes32 digest, bytes calldata signature) internal pure returns (address) {
+        require(signature.length == 65, "Invalid signature length");
+        bytes32 r = bytes32(signature[0:32]);
+        bytes32 s = bytes32(signature[32:64]);
+        uint8 v = uint8(signature[64]);
+        if (v < 27) {
+            v += 27;
+        }
+        // Reject malleable high-s signatures (EIP-2)
+        require(uint256(s) <= 0x7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF5D576E7357A4501DDFE92F46681B20A0, "Invalid signature");
+        address signer = ecrecover(digest, v, r, s);
+        require(signer != address(0), "Invalid signature");
+        return signer;
+    }
 }
 
 interface IERC20 {
     function transfer(address to, uint256 amount) external returns (bool);
     function transferFrom(address from, address to, uint256 amount) external returns (bool);
-}
\ No newline at end of file
+}
@@ -15,14 +15,23 @@ async function main() {
     throw new Error(`USDC address not configured for network: ${network}`);
   }
   
-  const FastDEXBridge = await hre.ethers.getContractFactory("FastDEXBridge");
-  const bridge = await FastDEXBridge.deploy(usdcAddress);
+  // Withdrawal vouchers need `threshold` signatures from these relayers; use the
+  // same set as the bridge tracker's `SetRelayers`
+  const relayers = (process.env.RELAYERS || "").split(",").map(r => r.trim()).filter(r => r);
+  const threshold = Number(process.env.RELAYER_THRESHOLD || relayers.length);
+  if (relayers.length === 0 || threshold < 1 || threshold > relayers.length) {
+    throw new Error("Set RELAYERS (comma-separated addresses) and RELAYER_THRESHOLD (1..number of relayers)");
+  }
+  
+  const Vault = await hre.ethers.getContractFactory("Vault");
+  const bridge = await Vault.deploy(usdcAddress, relayers, threshold);
   
   await bridge.waitForDeployment();
   const address = await bridge.getAddress();
   
-  console.log(`FastDEXBridge deployed to: ${address}`);
+  console.log(`Vault deployed to: ${address}`);
   console.log(`USDC address: ${usdcAddress}`);
+  console.log(`Relayers: ${relayers.join(", ")} (threshold ${threshold})`);
   console.log(`Network: ${network}`);
   
   // Save deployment info
@@ -31,6 +40,8 @@ async function main() {
     network,
     bridgeAddress: address,
     usdcAddress,
+    relayers,
+    threshold,
     deployedAt: new Date().toISOString()
   };
   